@@ -0,0 +1,381 @@
+//! Derive macro for `mbrs::map::ModbusMap`, see the documentation of the trait
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::ParseStream;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Fields, Ident, LitBool, LitInt, LitStr, Token,
+    Type,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Coil,
+    Discrete,
+    Holding,
+    Input,
+}
+
+impl Kind {
+    fn from_attr(ident: &Ident) -> Option<Self> {
+        match ident.to_string().as_str() {
+            "coil" => Some(Kind::Coil),
+            "discrete" => Some(Kind::Discrete),
+            "holding" => Some(Kind::Holding),
+            "input" => Some(Kind::Input),
+            _ => None,
+        }
+    }
+
+    fn table(self) -> &'static str {
+        match self {
+            Kind::Coil => "coils",
+            Kind::Discrete => "discrete inputs",
+            Kind::Holding => "holding registers",
+            Kind::Input => "input registers",
+        }
+    }
+
+    fn is_reg(self) -> bool {
+        matches!(self, Kind::Holding | Kind::Input)
+    }
+
+    fn is_writable(self) -> bool {
+        matches!(self, Kind::Coil | Kind::Holding)
+    }
+}
+
+/// Options of a register field
+#[derive(Default)]
+struct RegOpts {
+    order: Option<Ident>,
+    factor: Option<f32>,
+    offset: Option<f32>,
+    signed: Option<bool>,
+    min: Option<f32>,
+    max: Option<f32>,
+}
+
+impl RegOpts {
+    fn is_scaled(&self) -> bool {
+        self.factor.is_some()
+            || self.offset.is_some()
+            || self.signed.is_some()
+            || self.min.is_some()
+            || self.max.is_some()
+    }
+}
+
+struct Point {
+    kind: Kind,
+    address: u16,
+    field: Ident,
+    ty: Type,
+    opts: RegOpts,
+    span: Span,
+}
+
+/// Parse a possibly negative number literal
+fn parse_number(input: ParseStream) -> syn::Result<f32> {
+    let neg = input.parse::<Option<Token![-]>>()?.is_some();
+    let lit: syn::Lit = input.parse()?;
+    let value = match &lit {
+        syn::Lit::Float(f) => f.base10_parse::<f32>()?,
+        syn::Lit::Int(i) => i.base10_parse::<f32>()?,
+        _ => return Err(Error::new(lit.span(), "expected a number")),
+    };
+
+    Ok(if neg { -value } else { value })
+}
+
+fn parse_order(lit: &LitStr) -> syn::Result<Ident> {
+    let variant = match lit.value().to_uppercase().as_str() {
+        "ABCD" => "Abcd",
+        "CDAB" => "Cdab",
+        "BADC" => "Badc",
+        "DCBA" => "Dcba",
+        _ => {
+            return Err(Error::new(
+                lit.span(),
+                "expected one of \"ABCD\", \"CDAB\", \"BADC\" or \"DCBA\"",
+            ))
+        }
+    };
+
+    Ok(Ident::new(variant, lit.span()))
+}
+
+/// Parse `(address, key = value, ..)`
+fn parse_args(kind: Kind, input: ParseStream) -> syn::Result<(u16, RegOpts)> {
+    let address = input.parse::<LitInt>()?.base10_parse::<u16>()?;
+    let mut opts = RegOpts::default();
+
+    while !input.is_empty() {
+        input.parse::<Token![,]>()?;
+        if input.is_empty() {
+            break;
+        }
+
+        let key: Ident = input.parse()?;
+        if !kind.is_reg() {
+            return Err(Error::new(key.span(), "coils don't take any options"));
+        }
+        input.parse::<Token![=]>()?;
+
+        match key.to_string().as_str() {
+            "order" => opts.order = Some(parse_order(&input.parse()?)?),
+            "scale" => opts.factor = Some(parse_number(input)?),
+            "offset" => opts.offset = Some(parse_number(input)?),
+            "signed" => opts.signed = Some(input.parse::<LitBool>()?.value),
+            "min" => opts.min = Some(parse_number(input)?),
+            "max" => opts.max = Some(parse_number(input)?),
+            _ => {
+                return Err(Error::new(
+                    key.span(),
+                    "unknown option, expected one of `order`, `scale`, `offset`, `signed`, `min` or `max`",
+                ))
+            }
+        }
+    }
+
+    Ok((address, opts))
+}
+
+fn opt_f32(value: Option<f32>) -> TokenStream2 {
+    match value {
+        Some(v) => {
+            let v = Literal::f32_suffixed(v);
+            quote!(::core::option::Option::Some(#v))
+        }
+        None => quote!(::core::option::Option::None),
+    }
+}
+
+/// Number of addresses spanned by a point, as a constant expression
+fn size(point: &Point) -> TokenStream2 {
+    let ty = &point.ty;
+    match point.kind.is_reg() && !point.opts.is_scaled() {
+        true => quote!((<#ty as ::mbrs::reg::Value>::SIZE as u32)),
+        false => quote!(1u32),
+    }
+}
+
+fn coil_descriptor(point: &Point) -> TokenStream2 {
+    let address = point.address;
+    let field = &point.field;
+
+    let write = match point.kind.is_writable() {
+        true => quote! {
+            write: ::core::option::Option::Some(::mbrs::coil::WriteMethod::Fn(
+                ::std::boxed::Box::new(move |v| #field.set(v)),
+            )),
+        },
+        false => quote!(),
+    };
+
+    quote! {
+        ::mbrs::coil::Descriptor {
+            address: #address,
+            read: ::core::option::Option::Some(::mbrs::coil::ReadMethod::Fn(
+                ::std::boxed::Box::new(move || #field.get()),
+            )),
+            #write
+            ..::core::default::Default::default()
+        }
+    }
+}
+
+fn reg_descriptor(point: &Point) -> TokenStream2 {
+    let address = point.address;
+    let field = &point.field;
+    let ty = &point.ty;
+    let opts = &point.opts;
+
+    let source = quote!(::mbrs::reg::Source::Fn(::std::boxed::Box::new(move || #field.get())));
+    let sink = quote!(::mbrs::reg::Sink::Fn(::std::boxed::Box::new(move |v| #field.set(v))));
+
+    let (read, write, scale) = if opts.is_scaled() {
+        let factor = Literal::f32_suffixed(opts.factor.unwrap_or(1.0));
+        let offset = Literal::f32_suffixed(opts.offset.unwrap_or(0.0));
+        let signed = opts.signed.unwrap_or(false);
+        let min = opt_f32(opts.min);
+        let max = opt_f32(opts.max);
+        (
+            quote!(::mbrs::reg::ReadMethod::Scaled(#source)),
+            quote!(::mbrs::reg::WriteMethod::Scaled(#sink)),
+            quote! {
+                scale: ::mbrs::reg::Scale {
+                    factor: #factor,
+                    offset: #offset,
+                    signed: #signed,
+                    min: #min,
+                    max: #max,
+                },
+            },
+        )
+    } else {
+        (
+            quote!(<#ty as ::mbrs::reg::Value>::read_method(#source)),
+            quote!(<#ty as ::mbrs::reg::Value>::write_method(#sink)),
+            quote!(),
+        )
+    };
+
+    let write = match point.kind.is_writable() {
+        true => quote!(write: ::core::option::Option::Some(#write),),
+        false => quote!(),
+    };
+    let order = match &opts.order {
+        Some(order) => quote!(order: ::mbrs::reg::Order::#order,),
+        None => quote!(),
+    };
+
+    quote! {
+        ::mbrs::reg::Descriptor {
+            address: #address,
+            read: ::core::option::Option::Some(#read),
+            #write
+            #order
+            #scale
+            ..::core::default::Default::default()
+        }
+    }
+}
+
+/// Sort the points of a table and check for duplicates
+///
+/// Overlapping spans can only be checked once the sizes of the types are known,
+/// so those checks are returned as constant assertions.
+fn sort_table(points: &mut [&Point]) -> syn::Result<TokenStream2> {
+    points.sort_by_key(|p| p.address);
+
+    let mut checks = TokenStream2::new();
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let table = a.kind.table();
+
+        if a.address == b.address {
+            return Err(Error::new(
+                b.span,
+                format!(
+                    "{table}: `{}` has the same address as `{}`",
+                    b.field, a.field
+                ),
+            ));
+        }
+
+        let (a_addr, b_addr) = (a.address as u32, b.address as u32);
+        let size = size(a);
+        let msg = format!("{table}: `{}` overlaps `{}`", a.field, b.field);
+        checks.extend(quote! {
+            let end = #a_addr + #size;
+            assert!(end <= #b_addr, #msg);
+        });
+    }
+
+    if let Some(last) = points.last() {
+        let addr = last.address as u32;
+        let size = size(last);
+        let msg = format!("{}: `{}` spans past 0xFFFF", last.kind.table(), last.field);
+        checks.extend(quote! {
+            let end = #addr + #size;
+            assert!(end <= 0x10000, #msg);
+        });
+    }
+
+    Ok(checks)
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "ModbusMap can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "ModbusMap can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut points = Vec::new();
+    let mut mapped = Vec::new();
+    for field in fields {
+        let ident = field.ident.clone().unwrap();
+        for attr in &field.attrs {
+            let Some(kind) = attr.path().get_ident().and_then(Kind::from_attr) else {
+                continue;
+            };
+
+            let (address, opts) =
+                attr.parse_args_with(|input: ParseStream| parse_args(kind, input))?;
+            points.push(Point {
+                kind,
+                address,
+                field: ident.clone(),
+                ty: field.ty.clone(),
+                opts,
+                span: attr.pound_token.span,
+            });
+
+            if !mapped.contains(&ident) {
+                mapped.push(ident.clone());
+            }
+        }
+    }
+
+    let mut checks = TokenStream2::new();
+    let mut tables = Vec::new();
+    for kind in [Kind::Discrete, Kind::Coil, Kind::Input, Kind::Holding] {
+        let mut table: Vec<&Point> = points.iter().filter(|p| p.kind == kind).collect();
+        checks.extend(sort_table(&mut table)?);
+
+        let descriptors = table.iter().map(|p| match kind.is_reg() {
+            true => reg_descriptor(p),
+            false => coil_descriptor(p),
+        });
+        tables.push(quote!(::std::vec![#(#descriptors),*]));
+    }
+    let [disc_inputs, coils, input_regs, holding_regs] = &tables[..] else {
+        unreachable!();
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::mbrs::map::ModbusMap for #name #ty_generics #where_clause {
+            fn modbus_map(&mut self) -> ::mbrs::map::Map<'_> {
+                let Self { #(#mapped,)* .. } = self;
+                #(let #mapped = ::std::cell::Cell::from_mut(#mapped);)*
+
+                ::mbrs::map::Map {
+                    disc_inputs: #disc_inputs,
+                    coils: #coils,
+                    input_regs: #input_regs,
+                    holding_regs: #holding_regs,
+                }
+            }
+        }
+
+        const _: () = {
+            #checks
+        };
+    })
+}
+
+#[proc_macro_derive(ModbusMap, attributes(coil, discrete, holding, input))]
+pub fn derive_modbus_map(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
@@ -1,4 +1,4 @@
-use crate::{crc, pdu, Instance};
+use crate::{crc, pdu, Counters, Instance, Outcome, Reason};
 
 /// The minimum size of a valid Modbus ADU buffer
 ///
@@ -19,9 +19,19 @@ pub const SIZE_MAX: usize = 256;
 pub const SLAVE_ADDR_MIN: u8 = 1;
 pub const SLAVE_ADDR_MAX: u8 = 247;
 
-const SLAVE_ADDR_BROADCAST: u8 = 0;
+pub(crate) const SLAVE_ADDR_BROADCAST: u8 = 0;
 const SLAVE_ADDR_DEFAULT_RESP: u8 = 248;
 
+/// Whether a serial request sent to `recv_slave_addr` should be handled by slave `slave_addr`
+pub(crate) fn is_addressed(slave_addr: u8, recv_slave_addr: u8) -> bool {
+    match recv_slave_addr {
+        x if x == slave_addr => true,
+        x if x == SLAVE_ADDR_BROADCAST => true,
+        x if x == SLAVE_ADDR_DEFAULT_RESP => true,
+        _ => false,
+    }
+}
+
 fn prep_res(slave_addr: u8, res: &mut [u8; SIZE_MAX], pdu_size: usize) -> usize {
     res[0] = slave_addr;
     let res_size = 1 + pdu_size;
@@ -34,36 +44,57 @@ fn prep_res(slave_addr: u8, res: &mut [u8; SIZE_MAX], pdu_size: usize) -> usize
     res_size + 2
 }
 
-pub fn handle_req<'a>(inst: &'a Instance<'a>, buf: &[u8], res: &mut [u8; SIZE_MAX]) -> usize {
-    if !inst.serial.is_some() || buf.len() < SIZE_MIN || buf.len() > SIZE_MAX {
-        return 0;
+pub fn handle_req(inst: &mut Instance, buf: &[u8], res: &mut [u8; SIZE_MAX]) -> Outcome {
+    let slave_addr = match &inst.serial {
+        Some(serial) => serial.slave_addr,
+        None => return Outcome::NoResponse(Reason::NotConfigured),
+    };
+
+    Counters::inc(&mut inst.counters.bus_messages);
+
+    if buf.len() < SIZE_MIN {
+        Counters::inc(&mut inst.counters.bus_comm_errors);
+        return Outcome::NoResponse(Reason::Undersized);
+    }
+    if buf.len() > SIZE_MAX {
+        Counters::inc(&mut inst.counters.overruns);
+        return Outcome::NoResponse(Reason::Oversized);
     }
 
     // Check CRC before slave address to monitor the overall health of the bus, not just this device
     let recv_crc = u16::from_le_bytes(buf[(buf.len() - 2)..].try_into().unwrap());
     if recv_crc != crc::crc16(&buf[..buf.len() - 2]) {
-        return 0;
+        Counters::inc(&mut inst.counters.bus_comm_errors);
+        return Outcome::NoResponse(Reason::Crc);
     }
 
     let recv_slave_addr = buf[0];
-    if !match recv_slave_addr {
-        x if x == inst.serial.as_ref().unwrap().slave_addr => true,
-        x if x == SLAVE_ADDR_BROADCAST => true,
-        x if x == SLAVE_ADDR_DEFAULT_RESP => true,
-        _ => false,
-    } {
-        return 0;
+    if !is_addressed(slave_addr, recv_slave_addr) {
+        return Outcome::NoResponse(Reason::OtherSlave(recv_slave_addr));
     }
+    Counters::inc(&mut inst.counters.server_messages);
 
-    let pdu_size = pdu::handle_req(
+    let pdu_size = pdu::handle_req_with(
         inst,
+        &pdu::Transport::Serial {
+            slave_addr: recv_slave_addr,
+        },
         &buf[1..buf.len() - 2],
         (&mut res[1..(1 + pdu::SIZE_MAX)]).try_into().unwrap(),
     );
 
-    if pdu_size == 0 || recv_slave_addr == SLAVE_ADDR_BROADCAST {
-        return 0;
+    let pdu_size = match pdu_size {
+        Outcome::Response(size) => size,
+        no_response => {
+            Counters::inc(&mut inst.counters.no_responses);
+            return no_response;
+        }
+    };
+
+    if recv_slave_addr == SLAVE_ADDR_BROADCAST {
+        Counters::inc(&mut inst.counters.no_responses);
+        return Outcome::NoResponse(Reason::Broadcast);
     }
 
-    prep_res(recv_slave_addr, res, pdu_size)
+    Outcome::Response(prep_res(recv_slave_addr, res, pdu_size))
 }
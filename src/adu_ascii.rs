@@ -0,0 +1,239 @@
+use std::time::Duration;
+
+use crate::adu::{self, SLAVE_ADDR_BROADCAST};
+use crate::{pdu, Counters, Instance, Outcome, Reason};
+
+// Modbus ASCII frame
+// - ':' start
+// - Slave address, PDU and LRC, each byte as two hex characters
+// - CR LF end
+
+pub const START: u8 = b':';
+pub const END: [u8; 2] = *b"\r\n";
+
+/// The minimum size of a valid ASCII frame
+///
+/// - 1 Start
+/// - 2 Slave address
+/// - 2 Function code
+/// - 2 LRC
+/// - 2 End
+const SIZE_MIN: usize = 9;
+
+/// The maximum size of a valid ASCII frame
+///
+/// - 1 Start
+/// - 2 Slave address
+/// - 506 PDU
+/// - 2 LRC
+/// - 2 End
+pub const SIZE_MAX: usize = 1 + 2 * (1 + pdu::SIZE_MAX + 1) + 2;
+
+/// Default longest silence allowed between two characters of a frame
+pub const CHAR_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Generate a Modbus LRC from a buffer
+///
+/// # Examples
+///
+/// ```
+/// let buf = [0x01, 0x03, 0x00, 0x00, 0x00, 0x01];
+/// let lrc = mbrs::adu_ascii::lrc(&buf);
+/// assert_eq!(lrc, 0xFB);
+/// ```
+pub fn lrc(buf: &[u8]) -> u8 {
+    buf.iter()
+        .fold(0u8, |lrc, v| lrc.wrapping_add(*v))
+        .wrapping_neg()
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        _ => None,
+    }
+}
+
+fn hex_char(v: u8) -> u8 {
+    b"0123456789ABCDEF"[v as usize & 0x0F]
+}
+
+/// Decode an ASCII frame into its binary form, slave address, PDU and LRC
+///
+/// Returns the size of the binary frame. The LRC is not checked.
+pub fn decode(frame: &[u8], buf: &mut [u8; adu::SIZE_MAX]) -> Result<usize, Reason> {
+    let body = frame
+        .strip_prefix(&[START])
+        .and_then(|f| f.strip_suffix(&END))
+        .ok_or(Reason::Encoding)?;
+    if body.len() % 2 != 0 || body.len() / 2 > buf.len() {
+        return Err(Reason::Encoding);
+    }
+
+    for (i, pair) in body.chunks_exact(2).enumerate() {
+        let hi = hex_value(pair[0]).ok_or(Reason::Encoding)?;
+        let lo = hex_value(pair[1]).ok_or(Reason::Encoding)?;
+        buf[i] = hi << 4 | lo;
+    }
+
+    Ok(body.len() / 2)
+}
+
+/// Encode a slave address and PDU as an ASCII frame, appending the LRC
+///
+/// Returns the size of the frame.
+/// Panics if `res` can't hold the frame.
+pub fn encode(slave_addr: u8, pdu: &[u8], res: &mut [u8]) -> usize {
+    let mut size = 0;
+    let mut put = |c: u8| {
+        res[size] = c;
+        size += 1;
+    };
+
+    put(START);
+    let lrc = lrc(pdu).wrapping_sub(slave_addr);
+    for v in [slave_addr].iter().chain(pdu).chain([lrc].iter()) {
+        put(hex_char(v >> 4));
+        put(hex_char(*v));
+    }
+    END.iter().for_each(|c| put(*c));
+
+    size
+}
+
+pub fn handle_req(inst: &mut Instance, buf: &[u8], res: &mut [u8; SIZE_MAX]) -> Outcome {
+    let slave_addr = match &inst.serial {
+        Some(serial) => serial.slave_addr,
+        None => return Outcome::NoResponse(Reason::NotConfigured),
+    };
+
+    Counters::inc(&mut inst.counters.bus_messages);
+
+    if buf.len() < SIZE_MIN {
+        Counters::inc(&mut inst.counters.bus_comm_errors);
+        return Outcome::NoResponse(Reason::Undersized);
+    }
+    if buf.len() > SIZE_MAX {
+        Counters::inc(&mut inst.counters.overruns);
+        return Outcome::NoResponse(Reason::Oversized);
+    }
+
+    let mut req = [0; adu::SIZE_MAX];
+    let req_size = match decode(buf, &mut req) {
+        Ok(size) => size,
+        Err(reason) => return Outcome::NoResponse(reason),
+    };
+    let req = &req[..req_size];
+
+    // Check LRC before slave address to monitor the overall health of the bus, not just this device
+    if lrc(req) != 0 {
+        Counters::inc(&mut inst.counters.bus_comm_errors);
+        return Outcome::NoResponse(Reason::Lrc);
+    }
+
+    let recv_slave_addr = req[0];
+    if !adu::is_addressed(slave_addr, recv_slave_addr) {
+        return Outcome::NoResponse(Reason::OtherSlave(recv_slave_addr));
+    }
+    Counters::inc(&mut inst.counters.server_messages);
+
+    let mut pdu_res = [0; pdu::SIZE_MAX];
+    let pdu_size = pdu::handle_req_with(
+        inst,
+        &pdu::Transport::Serial {
+            slave_addr: recv_slave_addr,
+        },
+        &req[1..req.len() - 1],
+        &mut pdu_res,
+    );
+
+    let pdu_size = match pdu_size {
+        Outcome::Response(size) => size,
+        no_response => {
+            Counters::inc(&mut inst.counters.no_responses);
+            return no_response;
+        }
+    };
+
+    if recv_slave_addr == SLAVE_ADDR_BROADCAST {
+        Counters::inc(&mut inst.counters.no_responses);
+        return Outcome::NoResponse(Reason::Broadcast);
+    }
+
+    Outcome::Response(encode(recv_slave_addr, &pdu_res[..pdu_size], res))
+}
+
+/// Assembles ASCII frames from a stream of timestamped bytes
+///
+/// A ':' always starts a new frame, dropping any partial one,
+/// and a partial frame is also dropped when the silence before a character exceeds the timeout.
+pub struct FrameReceiver {
+    timeout: Duration,
+    buf: [u8; SIZE_MAX],
+    len: usize,
+    last: Option<Duration>,
+}
+
+impl Default for FrameReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameReceiver {
+    /// Create a receiver with the default [`CHAR_TIMEOUT`]
+    pub fn new() -> Self {
+        Self::with_timeout(CHAR_TIMEOUT)
+    }
+
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            buf: [0; SIZE_MAX],
+            len: 0,
+            last: None,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Feed a byte received at `now`, see [`crate::rtu::FrameReceiver`] for how time is measured
+    ///
+    /// Returns the frame, delimiters included, once its LF is received.
+    pub fn push(&mut self, byte: u8, now: Duration) -> Option<&[u8]> {
+        let timed_out = self
+            .last
+            .is_some_and(|last| now.saturating_sub(last) > self.timeout);
+        self.last = Some(now);
+
+        if byte == START {
+            self.buf[0] = byte;
+            self.len = 1;
+            return None;
+        }
+        if self.len == 0 || timed_out || self.len == SIZE_MAX {
+            // Not inside a frame, wait for the next start
+            self.len = 0;
+            return None;
+        }
+
+        self.buf[self.len] = byte;
+        self.len += 1;
+        if self.buf[..self.len].ends_with(&END) {
+            let len = std::mem::take(&mut self.len);
+            return Some(&self.buf[..len]);
+        }
+
+        None
+    }
+
+    /// Drop any partially received frame
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.last = None;
+    }
+}
@@ -1,7 +1,9 @@
+use std::net::SocketAddr;
+
 use byteorder::{BigEndian, ByteOrder};
 
 use crate::pdu;
-use crate::Instance;
+use crate::{Instance, Outcome, Reason};
 
 /// Modbus Application Protocol (MBAP) header
 /// - Transaction id (2 bytes BE)
@@ -9,6 +11,7 @@ use crate::Instance;
 /// - Length (2 bytes BE) (The rest of this buffer; including unit id)
 /// - Unit id (1 byte) (Same as Modbus serial slave address)
 
+#[allow(clippy::empty_line_after_doc_comments)]
 const MBAP_POS_TRANS_ID: usize = 0;
 const MBAP_POS_PROT_ID: usize = 2;
 const MBAP_POS_LEN: usize = 4;
@@ -20,9 +23,28 @@ pub const PROT_ID: u16 = 0;
 pub const SIZE_MAX: usize = MBAP_SIZE + pdu::SIZE_MAX;
 pub const TCP_PORT: usize = 502;
 
-pub fn handle_req<'a>(inst: &'a Instance<'a>, buf: &[u8], res: &mut [u8; SIZE_MAX]) -> usize {
+pub fn handle_req(inst: &mut Instance, buf: &[u8], res: &mut [u8; SIZE_MAX]) -> Outcome {
+    handle(inst, buf, res, None)
+}
+
+/// Handle a request received from `peer`, which is passed on to middleware
+pub fn handle_req_from(
+    inst: &mut Instance,
+    buf: &[u8],
+    res: &mut [u8; SIZE_MAX],
+    peer: SocketAddr,
+) -> Outcome {
+    handle(inst, buf, res, Some(peer))
+}
+
+fn handle(
+    inst: &mut Instance,
+    buf: &[u8],
+    res: &mut [u8; SIZE_MAX],
+    peer: Option<SocketAddr>,
+) -> Outcome {
     if buf.len() < MBAP_SIZE + 1 {
-        return 0;
+        return Outcome::NoResponse(Reason::Undersized);
     }
 
     let transaction_id = BigEndian::read_u16(&buf[MBAP_POS_TRANS_ID..]);
@@ -31,22 +53,27 @@ pub fn handle_req<'a>(inst: &'a Instance<'a>, buf: &[u8], res: &mut [u8; SIZE_MA
     let unit_id = buf[MBAP_POS_UNIT_ID];
 
     if protocol_id != PROT_ID {
-        return 0;
+        return Outcome::NoResponse(Reason::ProtocolId(protocol_id));
     }
 
-    if length < 1 || length - 1 > pdu::SIZE_MAX || buf.len() < length - 1 + MBAP_SIZE {
-        return 0;
+    if length < 1 || length - 1 > pdu::SIZE_MAX {
+        return Outcome::NoResponse(Reason::Length);
+    }
+    if buf.len() < length - 1 + MBAP_SIZE {
+        return Outcome::NoResponse(Reason::Undersized);
     }
 
-    let pdu_size = pdu::handle_req(
+    let pdu_size = pdu::handle_req_with(
         inst,
+        &pdu::Transport::Tcp { unit_id, peer },
         &buf[MBAP_SIZE..(MBAP_SIZE + length - 1)],
         (&mut res[MBAP_SIZE..]).try_into().unwrap(),
     );
 
-    if pdu_size == 0 {
-        return 0;
-    }
+    let pdu_size = match pdu_size {
+        Outcome::Response(size) => size,
+        no_response => return no_response,
+    };
 
     // Build response MBAP
     BigEndian::write_u16(&mut res[MBAP_POS_TRANS_ID..], transaction_id);
@@ -54,5 +81,5 @@ pub fn handle_req<'a>(inst: &'a Instance<'a>, buf: &[u8], res: &mut [u8; SIZE_MA
     BigEndian::write_u16(&mut res[MBAP_POS_LEN..], 1 + pdu_size as u16);
     res[MBAP_POS_UNIT_ID] = unit_id;
 
-    MBAP_SIZE + pdu_size
+    Outcome::Response(MBAP_SIZE + pdu_size)
 }
@@ -0,0 +1,221 @@
+use std::fmt::Display;
+
+use crate::adu::{SLAVE_ADDR_MAX, SLAVE_ADDR_MIN};
+use crate::{coil, reg, Instance, SerialConfig};
+
+/// Point table of an instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    DiscInputs,
+    Coils,
+    InputRegs,
+    HoldingRegs,
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Table::DiscInputs => write!(f, "discrete inputs"),
+            Table::Coils => write!(f, "coils"),
+            Table::InputRegs => write!(f, "input registers"),
+            Table::HoldingRegs => write!(f, "holding registers"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    /// Two points share the same address
+    Duplicate { table: Table, address: u16 },
+    /// A point starts inside the span of the point before it
+    Overlap {
+        table: Table,
+        address: u16,
+        /// Start and end address of the point it overlaps
+        span: (u16, u16),
+    },
+    /// A point spans past the last address
+    OutOfRange { table: Table, address: u16 },
+    /// A point spans no addresses, such as an empty block, or more than 65535
+    InvalidSize { table: Table, address: u16 },
+    /// The read and write methods of a point span a different number of addresses
+    SpanMismatch { table: Table, address: u16 },
+    /// The serial slave address is outside of `SLAVE_ADDR_MIN..=SLAVE_ADDR_MAX`
+    SlaveAddr(u8),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::Duplicate { table, address } => {
+                write!(f, "{table}: duplicate address 0x{address:04X}")
+            }
+            BuildError::Overlap {
+                table,
+                address,
+                span: (start, end),
+            } => write!(
+                f,
+                "{table}: 0x{address:04X} overlaps 0x{start:04X}..=0x{end:04X}"
+            ),
+            BuildError::OutOfRange { table, address } => {
+                write!(f, "{table}: 0x{address:04X} spans past 0xFFFF")
+            }
+            BuildError::InvalidSize { table, address } => {
+                write!(
+                    f,
+                    "{table}: 0x{address:04X} is empty or spans over 65535 addresses"
+                )
+            }
+            BuildError::SpanMismatch { table, address } => write!(
+                f,
+                "{table}: 0x{address:04X} reads and writes a different number of addresses"
+            ),
+            BuildError::SlaveAddr(addr) => write!(
+                f,
+                "slave address {addr} is outside of {SLAVE_ADDR_MIN}..={SLAVE_ADDR_MAX}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Builds an [`Instance`] from point tables in any order
+///
+/// The tables are sorted in place and checked for duplicate and overlapping addresses,
+/// as well as points that can't be accessed, such as empty blocks.
+#[derive(Default)]
+pub struct InstanceBuilder<'a, 'b> {
+    disc_inputs: Option<&'a mut [coil::Descriptor<'b>]>,
+    coils: Option<&'a mut [coil::Descriptor<'b>]>,
+    input_regs: Option<&'a mut [reg::Descriptor<'b>]>,
+    holding_regs: Option<&'a mut [reg::Descriptor<'b>]>,
+    serial: Option<SerialConfig>,
+}
+
+/// Sort a table and make sure every point can be accessed and no two points cover the same address
+///
+/// `span` gives the start and number of addresses of a point, once `check` has accepted it.
+fn sort_and_check<T>(
+    table: Table,
+    points: &mut [T],
+    span: impl Fn(&T) -> (u16, u16),
+    check: impl Fn(Table, &T) -> Result<(), BuildError>,
+) -> Result<(), BuildError> {
+    points.sort_by_key(|p| span(p).0);
+
+    let mut prev: Option<(u16, u16)> = None;
+    for p in points.iter() {
+        check(table, p)?;
+
+        let (address, size) = span(p);
+        let end = address as u32 + size as u32 - 1;
+        if end > 0xFFFF {
+            return Err(BuildError::OutOfRange { table, address });
+        }
+
+        if let Some((prev_start, prev_end)) = prev {
+            if address == prev_start {
+                return Err(BuildError::Duplicate { table, address });
+            }
+            if address <= prev_end {
+                return Err(BuildError::Overlap {
+                    table,
+                    address,
+                    span: (prev_start, prev_end),
+                });
+            }
+        }
+
+        prev = Some((address, end as u16));
+    }
+
+    Ok(())
+}
+
+impl<'a, 'b> InstanceBuilder<'a, 'b> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn disc_inputs(mut self, points: &'a mut [coil::Descriptor<'b>]) -> Self {
+        self.disc_inputs = Some(points);
+        self
+    }
+
+    pub fn coils(mut self, points: &'a mut [coil::Descriptor<'b>]) -> Self {
+        self.coils = Some(points);
+        self
+    }
+
+    pub fn input_regs(mut self, points: &'a mut [reg::Descriptor<'b>]) -> Self {
+        self.input_regs = Some(points);
+        self
+    }
+
+    pub fn holding_regs(mut self, points: &'a mut [reg::Descriptor<'b>]) -> Self {
+        self.holding_regs = Some(points);
+        self
+    }
+
+    pub fn serial(mut self, serial: SerialConfig) -> Self {
+        self.serial = Some(serial);
+        self
+    }
+
+    pub fn build(mut self) -> Result<Instance<'a, 'b>, BuildError> {
+        let coil_span = |c: &coil::Descriptor| (c.address, c.size());
+        let reg_span = |r: &reg::Descriptor| (r.address, r.size());
+        let coil_check = |table, c: &coil::Descriptor| match c.check() {
+            Ok(()) => Ok(()),
+            Err(coil::Error::SpanMismatch) => Err(BuildError::SpanMismatch {
+                table,
+                address: c.address,
+            }),
+            Err(..) => Err(BuildError::InvalidSize {
+                table,
+                address: c.address,
+            }),
+        };
+        let reg_check = |table, r: &reg::Descriptor| match r.check() {
+            Ok(()) => Ok(()),
+            Err(reg::Error::SpanMismatch) => Err(BuildError::SpanMismatch {
+                table,
+                address: r.address,
+            }),
+            Err(..) => Err(BuildError::InvalidSize {
+                table,
+                address: r.address,
+            }),
+        };
+
+        if let Some(points) = self.disc_inputs.as_deref_mut() {
+            sort_and_check(Table::DiscInputs, points, coil_span, coil_check)?;
+        }
+        if let Some(points) = self.coils.as_deref_mut() {
+            sort_and_check(Table::Coils, points, coil_span, coil_check)?;
+        }
+        if let Some(points) = self.input_regs.as_deref_mut() {
+            sort_and_check(Table::InputRegs, points, reg_span, reg_check)?;
+        }
+        if let Some(points) = self.holding_regs.as_deref_mut() {
+            sort_and_check(Table::HoldingRegs, points, reg_span, reg_check)?;
+        }
+
+        if let Some(serial) = &self.serial {
+            if !(SLAVE_ADDR_MIN..=SLAVE_ADDR_MAX).contains(&serial.slave_addr) {
+                return Err(BuildError::SlaveAddr(serial.slave_addr));
+            }
+        }
+
+        Ok(Instance {
+            disc_inputs: self.disc_inputs.map(|p| &*p),
+            coils: self.coils,
+            input_regs: self.input_regs.map(|p| &*p),
+            holding_regs: self.holding_regs,
+            serial: self.serial,
+            ..Default::default()
+        })
+    }
+}
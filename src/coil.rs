@@ -1,24 +1,153 @@
 use std::fmt::Display;
 
+use crate::StatusCode;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     ReadNotSuppported,
     WriteNotSuppported,
     ReadLocked,
     WriteLocked,
+    /// The written value was rejected by the validator
+    Rejected(StatusCode),
+    /// The address is not covered by the descriptor
+    OutOfRange,
+    /// The descriptor spans no coils, or more than 65535,
+    /// or a packed block holds fewer coils than its size
+    InvalidSize,
+    /// The read and write methods span a different number of coils
+    SpanMismatch,
 }
 
 pub enum ReadMethod<'a> {
     Value(bool),
     Ref(&'a bool),
     Fn(Box<dyn Fn() -> bool + 'a>),
+    /// Block of consecutive coils starting at the descriptor address
+    Slice(&'a [bool]),
+    /// Packed block of consecutive coils starting at the descriptor address
+    ///
+    /// Coils are packed least significant bit first, like on the wire,
+    /// so the block must be at least `(size + 7) / 8` bytes long.
+    Bits {
+        /// Number of coils in the block
+        size: u16,
+        block: &'a [u8],
+    },
+}
+
+impl<'a> ReadMethod<'a> {
+    /// Number of coils held by the method, which may not fit in the address space
+    fn len(&self) -> usize {
+        match self {
+            ReadMethod::Value(..) | ReadMethod::Ref(..) | ReadMethod::Fn(..) => 1,
+            ReadMethod::Slice(block) => block.len(),
+            ReadMethod::Bits { size, .. } => *size as usize,
+        }
+    }
+
+    /// Whether a packed block holds all the coils of its size
+    fn fits(&self) -> bool {
+        match self {
+            ReadMethod::Bits { size, block } => *size as usize <= block.len() * 8,
+            _ => true,
+        }
+    }
+
+    /// Number of coils spanned by the method, saturating at 65535
+    pub fn size(&self) -> u16 {
+        u16::try_from(self.len()).unwrap_or(u16::MAX)
+    }
+
+    /// Read the coil at `offset` from the start of the method
+    fn read(&self, offset: usize) -> bool {
+        match self {
+            ReadMethod::Value(v) => *v,
+            ReadMethod::Ref(&r) => r,
+            ReadMethod::Fn(f) => f(),
+            ReadMethod::Slice(block) => block[offset],
+            ReadMethod::Bits { block, .. } => block[offset / 8] & 1 << (offset % 8) != 0,
+        }
+    }
 }
 
 pub enum WriteMethod<'a> {
     Ref(&'a mut bool),
     Fn(Box<dyn FnMut(bool) + 'a>),
+    /// Block of consecutive coils starting at the descriptor address
+    ///
+    /// The block can also be read if the descriptor has no read method.
+    Slice(&'a mut [bool]),
+    /// Packed block of consecutive coils starting at the descriptor address, see [`ReadMethod::Bits`]
+    ///
+    /// The block can also be read if the descriptor has no read method.
+    Bits {
+        /// Number of coils in the block
+        size: u16,
+        block: &'a mut [u8],
+    },
+}
+
+impl<'a> WriteMethod<'a> {
+    /// Number of coils held by the method, see [`ReadMethod::size`]
+    fn len(&self) -> usize {
+        match self {
+            WriteMethod::Ref(..) | WriteMethod::Fn(..) => 1,
+            WriteMethod::Slice(block) => block.len(),
+            WriteMethod::Bits { size, .. } => *size as usize,
+        }
+    }
+
+    /// Whether a packed block holds all the coils of its size
+    fn fits(&self) -> bool {
+        match self {
+            WriteMethod::Bits { size, block } => *size as usize <= block.len() * 8,
+            _ => true,
+        }
+    }
+
+    /// Number of coils spanned by the method, saturating at 65535
+    pub fn size(&self) -> u16 {
+        u16::try_from(self.len()).unwrap_or(u16::MAX)
+    }
+
+    /// Read back the coil at `offset` from a block
+    fn read(&self, offset: usize) -> Option<bool> {
+        match self {
+            WriteMethod::Ref(..) | WriteMethod::Fn(..) => None,
+            WriteMethod::Slice(block) => Some(block[offset]),
+            WriteMethod::Bits { block, .. } => Some(block[offset / 8] & 1 << (offset % 8) != 0),
+        }
+    }
+
+    /// Write the coil at `offset` from the start of the method
+    fn write(&mut self, offset: usize, value: bool) {
+        match self {
+            WriteMethod::Ref(r) => **r = value,
+            WriteMethod::Fn(f) => f(value),
+            WriteMethod::Slice(block) => block[offset] = value,
+            WriteMethod::Bits { block, .. } => match value {
+                true => block[offset / 8] |= 1 << (offset % 8),
+                false => block[offset / 8] &= !(1 << (offset % 8)),
+            },
+        }
+    }
+}
+
+/// A coil about to be written, see [`crate::Instance::stage_coil_write`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub address: u16,
+    /// Current value, `None` if the coil can't be read
+    pub old: Option<bool>,
+    pub new: bool,
 }
 
+/// Checks a value before it is written
+///
+/// The returned status code is sent back as the exception response.
+pub type Validator<'a> = dyn Fn(bool) -> Result<(), StatusCode> + 'a;
+
 pub struct Descriptor<'a> {
     pub address: u16,
     pub read: Option<ReadMethod<'a>>,
@@ -27,9 +156,12 @@ pub struct Descriptor<'a> {
     pub rlock: Option<Box<dyn Fn() -> bool + 'a>>,
     pub wlock: Option<Box<dyn Fn() -> bool + 'a>>,
 
+    pub validator: Option<Box<Validator<'a>>>,
+
     pub post_write: Option<Box<dyn FnMut() + 'a>>,
 }
 
+#[allow(clippy::derivable_impls)]
 impl<'a> Default for Descriptor<'a> {
     fn default() -> Self {
         Self {
@@ -38,6 +170,7 @@ impl<'a> Default for Descriptor<'a> {
             write: Default::default(),
             rlock: Default::default(),
             wlock: Default::default(),
+            validator: Default::default(),
             post_write: Default::default(),
         }
     }
@@ -51,6 +184,7 @@ impl<'a> PartialEq for Descriptor<'a> {
 
 impl<'a> Eq for Descriptor<'a> {}
 
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl<'a> PartialOrd for Descriptor<'a> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         self.address.partial_cmp(&other.address)
@@ -69,49 +203,117 @@ impl<'a> Display for Descriptor<'a> {
     }
 }
 
-/// Finds a coild by it's address
+/// Finds the coil, or block of coils, spanning an address
 ///
 /// This uses binary search, so all coils must be sorted in ascending order by address
 pub fn find<'a>(address: u16, coils: &'a [Descriptor<'a>]) -> Option<&'a Descriptor<'a>> {
-    match coils.binary_search_by(|coil| coil.address.cmp(&address)) {
-        Ok(ix) => Some(&coils[ix]),
-        Err(..) => None,
+    let ix = coils
+        .partition_point(|coil| coil.address <= address)
+        .checked_sub(1)?;
+    match address <= coils[ix].end_address() {
+        true => Some(&coils[ix]),
+        false => None,
     }
 }
 
-/// Finds a coild by it's address and get a mutable reference to it
+/// Finds the coil, or block of coils, spanning an address and get a mutable reference to it
 ///
 /// This uses binary search, so all coils must be sorted in ascending order by address
 pub fn find_mut<'a, 'b>(
     address: u16,
     coils: &'a mut [Descriptor<'b>],
 ) -> Option<&'a mut Descriptor<'b>> {
-    match coils.binary_search_by(|coil| coil.address.cmp(&address)) {
-        Ok(ix) => Some(&mut coils[ix]),
-        Err(..) => None,
+    let ix = coils
+        .partition_point(|coil| coil.address <= address)
+        .checked_sub(1)?;
+    match address <= coils[ix].end_address() {
+        true => Some(&mut coils[ix]),
+        false => None,
     }
 }
 
 impl<'a> Descriptor<'a> {
+    /// Number of consecutive coils spanned by this descriptor
+    pub fn size(&self) -> u16 {
+        match (&self.read, &self.write) {
+            (Some(method), _) => method.size(),
+            (None, Some(method)) => method.size(),
+            (None, None) => 1,
+        }
+    }
+
+    /// Whether the read and write methods span the same number of coils
+    pub fn spans_match(&self) -> bool {
+        match (&self.read, &self.write) {
+            (Some(read), Some(write)) => read.len() == write.len(),
+            _ => true,
+        }
+    }
+
+    /// Check that the descriptor can be accessed
+    ///
+    /// Blocks must hold between 1 and 65535 coils,
+    /// packed blocks must be long enough for their size,
+    /// and the read and write methods must span the same number of coils.
+    pub fn check(&self) -> Result<(), Error> {
+        let read = self.read.as_ref().map(ReadMethod::len);
+        let write = self.write.as_ref().map(WriteMethod::len);
+        if read
+            .into_iter()
+            .chain(write)
+            .any(|len| len == 0 || len > u16::MAX as usize)
+        {
+            return Err(Error::InvalidSize);
+        }
+
+        let read_fits = self.read.as_ref().is_none_or(ReadMethod::fits);
+        let write_fits = self.write.as_ref().is_none_or(WriteMethod::fits);
+        if !read_fits || !write_fits {
+            return Err(Error::InvalidSize);
+        }
+
+        match self.spans_match() {
+            true => Ok(()),
+            false => Err(Error::SpanMismatch),
+        }
+    }
+
+    /// Address of the last coil spanned by this descriptor
+    pub fn end_address(&self) -> u16 {
+        self.address.saturating_add(self.size().saturating_sub(1))
+    }
+
+    /// Offset of `address` from the start of this descriptor
+    fn offset(&self, address: u16) -> Result<usize, Error> {
+        match address >= self.address && address <= self.end_address() {
+            true => Ok((address - self.address) as usize),
+            false => Err(Error::OutOfRange),
+        }
+    }
+
     pub fn read_allowed(&self) -> bool {
         match &self.rlock {
-            Some(rlock) => rlock(),
+            Some(rlock) => !rlock(),
             None => true,
         }
     }
 
     pub fn read(&self) -> Result<bool, Error> {
+        self.read_at(self.address)
+    }
+
+    /// Read the coil at `address`, which must be spanned by this descriptor
+    pub fn read_at(&self, address: u16) -> Result<bool, Error> {
         if !self.read_allowed() {
             return Err(Error::ReadLocked);
         }
 
-        match &self.read {
-            Some(method) => match method {
-                ReadMethod::Value(v) => Ok(*v),
-                ReadMethod::Ref(&r) => Ok(r),
-                ReadMethod::Fn(f) => Ok(f()),
-            },
-            None => Err(Error::ReadNotSuppported),
+        self.check()?;
+        let offset = self.offset(address)?;
+        match (&self.read, &self.write) {
+            (Some(method), _) => Ok(method.read(offset)),
+            (None, Some(method)) => method.read(offset).ok_or(Error::ReadNotSuppported),
+            (None, None) => Err(Error::ReadNotSuppported),
         }
     }
 
@@ -122,23 +324,35 @@ impl<'a> Descriptor<'a> {
         }
     }
 
-    pub fn write(&self, _value: bool) -> Result<(), Error> {
+    /// Run the validator, if any, on a value about to be written
+    pub fn validate(&self, value: bool) -> Result<(), Error> {
+        match &self.validator {
+            Some(validator) => validator(value).map_err(Error::Rejected),
+            None => Ok(()),
+        }
+    }
+
+    pub fn write(&mut self, value: bool) -> Result<(), Error> {
+        self.write_at(self.address, value)
+    }
+
+    /// Write the coil at `address`, which must be spanned by this descriptor
+    pub fn write_at(&mut self, address: u16, value: bool) -> Result<(), Error> {
         if !self.write_allowed() {
             return Err(Error::WriteLocked);
         }
 
-        match &self.write {
+        self.check()?;
+        self.validate(value)?;
+
+        let offset = self.offset(address)?;
+        match &mut self.write {
             Some(method) => {
-                match method {
-                    //WriteMethod::Ref(ref mut r) => **r = value,
-                    //WriteMethod::Fn(f) => f(value),
-                    WriteMethod::Ref(_r) => (),
-                    WriteMethod::Fn(_f) => (),
-                };
-
-                /*if let Some(ref mut cb) = self.post_write {
+                method.write(offset, value);
+
+                if let Some(cb) = &mut self.post_write {
                     cb();
-                }*/
+                }
 
                 Ok(())
             }
@@ -25,6 +25,86 @@ pub enum StatusCode {
     MemoryParityError = 0x08,
 }
 
+/// Result of handling a request
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// A response of the given size was written to the response buffer
+    Response(usize),
+    /// Nothing should be sent back
+    NoResponse(Reason),
+}
+
+impl Outcome {
+    /// Size of the response, zero if there is none
+    pub fn size(self) -> usize {
+        match self {
+            Outcome::Response(size) => size,
+            Outcome::NoResponse(..) => 0,
+        }
+    }
+}
+
+/// Why a request got no response
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Reason {
+    /// The frame is too small to hold a request
+    Undersized,
+    /// The frame is larger than the maximum frame size
+    Oversized,
+    /// The instance isn't configured for this transport
+    NotConfigured,
+    /// Serial frame failed the CRC check
+    Crc,
+    /// RTU frame had more than t1.5 of silence between two characters
+    Gap,
+    /// ASCII frame failed the LRC check
+    Lrc,
+    /// ASCII frame is missing its delimiters or holds invalid hex characters
+    Encoding,
+    /// Serial request addressed to another slave
+    OtherSlave(u8),
+    /// Serial broadcast request, which is handled but never answered
+    Broadcast,
+    /// MBAP header holds a protocol id other than Modbus
+    ProtocolId(u16),
+    /// MBAP header length doesn't match the frame
+    Length,
+    /// Request dropped by middleware
+    Dropped,
+    /// The instance is in listen only mode, see FC 08 Diagnostics
+    ListenOnly,
+}
+
+/// Communication counters defined by the spec, see FC 08 Diagnostics
+///
+/// All counters wrap around at 0xFFFF.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Counters {
+    /// Messages seen on the bus, sub-function 0x0B
+    pub bus_messages: u16,
+    /// Messages with a CRC error, sub-function 0x0C
+    pub bus_comm_errors: u16,
+    /// Exception responses returned, sub-function 0x0D
+    pub exceptions: u16,
+    /// Messages addressed to this slave or broadcast, sub-function 0x0E
+    pub server_messages: u16,
+    /// Messages not responded to, sub-function 0x0F
+    pub no_responses: u16,
+    /// Negative acknowledge exception responses returned, sub-function 0x10
+    pub naks: u16,
+    /// Slave device busy exception responses returned, sub-function 0x11
+    pub busy: u16,
+    /// Messages lost to a character overrun, sub-function 0x12
+    pub overruns: u16,
+}
+
+impl Counters {
+    /// Increment a counter, wrapping around at 0xFFFF
+    pub(crate) fn inc(counter: &mut u16) {
+        *counter = counter.wrapping_add(1);
+    }
+}
+
 /// Modbus error flag
 ///
 /// Added onto the function code for error responses
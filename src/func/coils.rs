@@ -1,18 +1,102 @@
+#![allow(clippy::legacy_numeric_constants)]
+
 use std::u16;
 
 use byteorder::{BigEndian, ByteOrder};
 
 use crate::coil::{self, Error};
 use crate::def::{FunctionCode, StatusCode};
+use crate::func::Cursor;
 use crate::pdu::PDUBuf;
 use crate::Instance;
 
-const COIL_ON: u16 = 0xFFFF;
+const COIL_ON: u16 = 0xFF00;
 const COIL_OFF: u16 = 0x0000;
 
 const MAX_READ_QUANTITY: u16 = 0x07D0;
 const MAX_WRITE_QUANTITY: u16 = 0x07B0;
 
+/// Ensure all coils in a range exist and accept their new values
+fn check_write(
+    coils: &[coil::Descriptor],
+    start_addr: u16,
+    values: &[bool],
+) -> Result<(), StatusCode> {
+    if start_addr as usize + values.len() > 0x10000 {
+        return Err(StatusCode::IllegalDataAddr);
+    }
+
+    let mut cursor = Cursor::new(coils, start_addr);
+    for (i, &value) in values.iter().enumerate() {
+        let addr = start_addr + i as u16;
+        match cursor.seek(coils, addr).map(|ix| &coils[ix]) {
+            Some(c) => {
+                if c.write.is_none() || !c.write_allowed() {
+                    return Err(StatusCode::IllegalDataAddr);
+                }
+                if c.check().is_err() {
+                    return Err(StatusCode::DeviceFail);
+                }
+                if let Err(Error::Rejected(status_code)) = c.validate(value) {
+                    return Err(status_code);
+                }
+            }
+            None => return Err(StatusCode::IllegalDataAddr),
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate a range of coils, pass the changes to the staging hook, write them and run the commit hook
+fn commit_write(inst: &mut Instance, start_addr: u16, values: &[bool]) -> StatusCode {
+    let coils = match inst.coils.as_deref_mut() {
+        Some(c) => c,
+        None => return StatusCode::DeviceFail,
+    };
+
+    if let Err(status_code) = check_write(coils, start_addr, values) {
+        return status_code;
+    }
+
+    if let Some(cb) = &mut inst.stage_coil_write {
+        let mut cursor = Cursor::new(coils, start_addr);
+        let changes: Vec<coil::Change> = values
+            .iter()
+            .enumerate()
+            .map(|(i, &new)| {
+                let address = start_addr + i as u16;
+                let old = cursor
+                    .seek(coils, address)
+                    .and_then(|ix| coils[ix].read_at(address).ok());
+                coil::Change { address, old, new }
+            })
+            .collect();
+
+        if cb(&changes).is_err() {
+            return StatusCode::DeviceFail;
+        }
+    }
+
+    let mut cursor = Cursor::new(coils, start_addr);
+    for (i, &value) in values.iter().enumerate() {
+        let addr = start_addr + i as u16;
+        match cursor.seek(coils, addr).map(|ix| &mut coils[ix]) {
+            Some(c) => match c.write_at(addr, value) {
+                Ok(()) => (),
+                Err(..) => return StatusCode::DeviceFail,
+            },
+            None => return StatusCode::DeviceFail,
+        };
+    }
+
+    if let Some(cb) = &mut inst.commit_coil_write {
+        cb();
+    }
+
+    StatusCode::Ok
+}
+
 pub fn read_multiple(
     inst: &Instance,
     buf: &[u8],
@@ -20,12 +104,12 @@ pub fn read_multiple(
     read_disc_inputs: bool,
 ) -> Result<StatusCode, ()> {
     let coils = if read_disc_inputs {
-        match &inst.disc_inputs {
+        match inst.disc_inputs {
             Some(c) => c,
             None => return Err(()),
         }
     } else {
-        match &inst.coils {
+        match inst.coils.as_deref() {
             Some(c) => c,
             None => return Err(()),
         }
@@ -48,13 +132,19 @@ pub fn read_multiple(
         return Ok(StatusCode::IllegalDataValue);
     }
 
+    if start_addr as u32 + quantity as u32 > 0x10000 {
+        return Ok(StatusCode::IllegalDataAddr);
+    }
+
     // If we read multiple coils and one of them don't exist,
     // we just leave it as zero.
     // We don't want to do this if the first coil is missing.
-    if coil::find(start_addr, coils).is_none() {
+    let mut cursor = Cursor::new(coils, start_addr);
+    if cursor.seek(coils, start_addr).is_none() {
         return Ok(StatusCode::IllegalDataAddr);
     }
 
+    #[allow(clippy::manual_div_ceil)]
     let byte_count = (quantity as usize + 7) / 8;
     res.p[1] = byte_count as u8;
     res.size = 2 + byte_count;
@@ -64,8 +154,9 @@ pub fn read_multiple(
 
     for i in 0..quantity {
         let addr = start_addr + i;
-        match coil::find(addr, coils) {
-            Some(c) => match c.read() {
+        // If coil doesn't exist, it's left as 0
+        if let Some(ix) = cursor.seek(coils, addr) {
+            match coils[ix].read_at(addr) {
                 Ok(v) => {
                     if v {
                         res.p[2 + (i as usize / 8)] |= 1 << (i % 8);
@@ -74,30 +165,24 @@ pub fn read_multiple(
                 Err(Error::ReadNotSuppported) => (), // Leave as 0
                 Err(Error::ReadLocked) => return Ok(StatusCode::IllegalDataAddr),
                 Err(..) => return Ok(StatusCode::DeviceFail),
-            },
-            None => (), // If coil doesn't exist, it's left as 0
-        };
+            }
+        }
     }
 
     Ok(StatusCode::Ok)
 }
 
-pub fn write_single<'a>(
-    inst: &'a Instance<'a>,
-    buf: &[u8],
-    res: &mut PDUBuf,
-) -> Result<StatusCode, ()> {
-    let coils = match &inst.coils {
-        Some(c) => c,
-        None => return Err(()),
-    };
+pub fn write_single(inst: &mut Instance, buf: &[u8], res: &mut PDUBuf) -> Result<StatusCode, ()> {
+    if inst.coils.is_none() {
+        return Err(());
+    }
 
     let [fc, addr_hi, addr_lo, v_hi, v_lo] = match <[u8; 5]>::try_from(buf) {
         Ok(v) => v,
         Err(..) => return Ok(StatusCode::IllegalDataValue),
     };
 
-    if fc != FunctionCode::WriteSingleReg as u8 {
+    if fc != FunctionCode::WriteSingleCoil as u8 {
         return Ok(StatusCode::DeviceFail);
     }
 
@@ -109,20 +194,11 @@ pub fn write_single<'a>(
         return Ok(StatusCode::IllegalDataValue);
     }
 
-    match coil::find(addr, coils) {
-        Some(c) => match c.write(value == COIL_ON) {
-            Ok(()) => (),
-            Err(Error::WriteNotSuppported) => return Ok(StatusCode::IllegalDataAddr),
-            Err(Error::WriteLocked) => return Ok(StatusCode::IllegalDataAddr),
-            Err(..) => return Ok(StatusCode::DeviceFail),
-        },
-        None => return Ok(StatusCode::IllegalDataAddr),
+    match commit_write(inst, addr, &[value == COIL_ON]) {
+        StatusCode::Ok => (),
+        status_code => return Ok(status_code),
     };
 
-    /*if let Some(cb) = &inst.commit_coil_write {
-        cb();
-    }*/
-
     BigEndian::write_u16(&mut res.p[1..], addr);
     BigEndian::write_u16(&mut res.p[3..], value);
     res.size = 5;
@@ -130,15 +206,11 @@ pub fn write_single<'a>(
     Ok(StatusCode::Ok)
 }
 
-pub fn write_multiple<'a>(
-    inst: &'a Instance<'a>,
-    buf: &[u8],
-    res: &mut PDUBuf,
-) -> Result<StatusCode, ()> {
-    let coils = match &inst.coils {
-        Some(c) => c,
-        None => return Err(()),
-    };
+#[allow(clippy::manual_div_ceil)]
+pub fn write_multiple(inst: &mut Instance, buf: &[u8], res: &mut PDUBuf) -> Result<StatusCode, ()> {
+    if inst.coils.is_none() {
+        return Err(());
+    }
 
     // Check that request data is at least big enough for fields
     // fc', 'addr', 'nr of coils', 'byte count' and 'data'... (u8 + u16 + u16 + u8 + ...)
@@ -166,35 +238,14 @@ pub fn write_multiple<'a>(
         return Ok(StatusCode::IllegalDataValue);
     }
 
-    // Ensure all coils exist and can be written to before writing anything
-    for i in 0..quantity {
-        let addr = start_addr + i;
-        match coil::find(addr, coils) {
-            Some(c) => {
-                if !c.write_allowed() {
-                    return Ok(StatusCode::IllegalDataAddr);
-                }
-            }
-            None => return Ok(StatusCode::IllegalDataAddr),
-        }
-    }
+    let values: Vec<bool> = (0..quantity as usize)
+        .map(|i| (buf[6 + i / 8] & 1 << (i % 8)) != 0)
+        .collect();
 
-    // Write coils
-    for i in 0..quantity {
-        let addr = start_addr + i;
-        let value = (buf[(6 + i / 8) as usize] & 1 << (i % 8)) != 0;
-        match coil::find(addr, coils) {
-            Some(c) => match c.write(value) {
-                Ok(()) => (),
-                Err(..) => return Ok(StatusCode::DeviceFail),
-            },
-            None => return Ok(StatusCode::DeviceFail),
-        };
-    }
-
-    /*if let Some(ref mut cb) = inst.commit_coil_write {
-        cb();
-    }*/
+    match commit_write(inst, start_addr, &values) {
+        StatusCode::Ok => (),
+        status_code => return Ok(status_code),
+    };
 
     BigEndian::write_u16(&mut res.p[1..], start_addr);
     BigEndian::write_u16(&mut res.p[3..], quantity);
@@ -0,0 +1,99 @@
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::def::{Counters, FunctionCode, StatusCode};
+use crate::pdu::PDUBuf;
+use crate::Instance;
+
+const RETURN_QUERY_DATA: u16 = 0x00;
+const RESTART_COMM: u16 = 0x01;
+const FORCE_LISTEN_ONLY: u16 = 0x04;
+const CLEAR_COUNTERS: u16 = 0x0A;
+const BUS_MESSAGE_COUNT: u16 = 0x0B;
+const BUS_COMM_ERROR_COUNT: u16 = 0x0C;
+const EXCEPTION_COUNT: u16 = 0x0D;
+const SERVER_MESSAGE_COUNT: u16 = 0x0E;
+const NO_RESPONSE_COUNT: u16 = 0x0F;
+const NAK_COUNT: u16 = 0x10;
+const BUSY_COUNT: u16 = 0x11;
+const OVERRUN_COUNT: u16 = 0x12;
+
+/// Restart data clearing the communications event log as well
+const RESTART_CLEAR_LOG: u16 = 0xFF00;
+
+/// Whether a request PDU is a Restart Communications Option
+pub fn is_restart(buf: &[u8]) -> bool {
+    matches!(buf, [fc, 0x00, 0x01, ..] if *fc == FunctionCode::Diagnostics as u8)
+}
+
+fn counter(counters: &Counters, sub_fn: u16) -> Option<u16> {
+    match sub_fn {
+        BUS_MESSAGE_COUNT => Some(counters.bus_messages),
+        BUS_COMM_ERROR_COUNT => Some(counters.bus_comm_errors),
+        EXCEPTION_COUNT => Some(counters.exceptions),
+        SERVER_MESSAGE_COUNT => Some(counters.server_messages),
+        NO_RESPONSE_COUNT => Some(counters.no_responses),
+        NAK_COUNT => Some(counters.naks),
+        BUSY_COUNT => Some(counters.busy),
+        OVERRUN_COUNT => Some(counters.overruns),
+        _ => None,
+    }
+}
+
+/// Echo the request back as the response
+fn echo(buf: &[u8], res: &mut PDUBuf) {
+    res.p[..buf.len()].copy_from_slice(buf);
+    res.size = buf.len();
+}
+
+pub fn handle(inst: &mut Instance, buf: &[u8], res: &mut PDUBuf) -> Result<StatusCode, ()> {
+    if buf.len() < 3 {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    if buf[0] != FunctionCode::Diagnostics as u8 {
+        return Ok(StatusCode::DeviceFail);
+    }
+
+    let sub_fn = BigEndian::read_u16(&buf[1..]);
+
+    // Return Query Data echoes any amount of data, all other sub-functions take a single word
+    if sub_fn == RETURN_QUERY_DATA {
+        echo(buf, res);
+        return Ok(StatusCode::Ok);
+    }
+
+    let value = counter(&inst.counters, sub_fn);
+    if value.is_none() && !matches!(sub_fn, RESTART_COMM | FORCE_LISTEN_ONLY | CLEAR_COUNTERS) {
+        return Err(());
+    }
+
+    let data = match <[u8; 2]>::try_from(&buf[3..]) {
+        Ok(v) => u16::from_be_bytes(v),
+        Err(..) => return Ok(StatusCode::IllegalDataValue),
+    };
+    let data_valid = match sub_fn {
+        RESTART_COMM => matches!(data, 0x0000 | RESTART_CLEAR_LOG),
+        _ => data == 0x0000,
+    };
+    if !data_valid {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    match sub_fn {
+        RESTART_COMM => inst.init(),
+        FORCE_LISTEN_ONLY => inst.listen_only = true,
+        CLEAR_COUNTERS => inst.clear_counters(),
+        _ => (),
+    }
+
+    match value {
+        Some(value) => {
+            res.p[1..3].copy_from_slice(&buf[1..3]);
+            BigEndian::write_u16(&mut res.p[3..], value);
+            res.size = 5;
+        }
+        None => echo(buf, res),
+    }
+
+    Ok(StatusCode::Ok)
+}
@@ -1,2 +1,61 @@
 pub mod coils;
+pub mod diag;
 pub mod regs;
+
+use crate::{coil, reg};
+
+/// A descriptor spanning one or more consecutive addresses
+pub trait Span {
+    fn start_address(&self) -> u16;
+    fn end_address(&self) -> u16;
+}
+
+impl Span for coil::Descriptor<'_> {
+    fn start_address(&self) -> u16 {
+        self.address
+    }
+
+    fn end_address(&self) -> u16 {
+        coil::Descriptor::end_address(self)
+    }
+}
+
+impl Span for reg::Descriptor<'_> {
+    fn start_address(&self) -> u16 {
+        self.address
+    }
+
+    fn end_address(&self) -> u16 {
+        reg::Descriptor::end_address(self)
+    }
+}
+
+/// Walks a sorted table in step with an ascending range of addresses
+///
+/// Only the start of the range is looked up with a binary search,
+/// after that each address is found by moving forward through the table.
+pub struct Cursor {
+    ix: usize,
+}
+
+impl Cursor {
+    pub fn new<T: Span>(table: &[T], start_addr: u16) -> Self {
+        Cursor {
+            ix: table.partition_point(|d| d.end_address() < start_addr),
+        }
+    }
+
+    /// Index of the descriptor spanning `address`
+    ///
+    /// Addresses must be visited in ascending order.
+    pub fn seek<T: Span>(&mut self, table: &[T], address: u16) -> Option<usize> {
+        while self.ix < table.len() && table[self.ix].end_address() < address {
+            self.ix += 1;
+        }
+
+        match table.get(self.ix) {
+            Some(d) if d.start_address() <= address => Some(self.ix),
+            _ => None,
+        }
+    }
+}
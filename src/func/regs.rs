@@ -1,40 +1,448 @@
+use byteorder::{BigEndian, ByteOrder};
+
 use crate::def::{FunctionCode, StatusCode};
+use crate::func::Cursor;
 use crate::pdu::PDUBuf;
+use crate::reg::{self, Error};
+use crate::Instance;
+
+const MAX_READ_QUANTITY: u16 = 0x007D;
+const MAX_WRITE_QUANTITY: u16 = 0x007B;
+const MAX_RW_WRITE_QUANTITY: u16 = 0x0079;
+
+/// Number of registers of `r` covered by an access from `addr` up to `end`
+///
+/// Blocks can be accessed partially, other values must be accessed as a whole.
+/// `r` must have passed [`reg::Descriptor::check`], so the span is never zero.
+fn span(r: &reg::Descriptor, addr: u32, end: u32) -> Result<u32, StatusCode> {
+    let r_end = r.address as u32 + r.size() as u32;
+    if r.is_block() {
+        return Ok(r_end.min(end) - addr);
+    }
+
+    if addr != r.address as u32 || r_end > end {
+        return Err(StatusCode::IllegalDataAddr);
+    }
+
+    Ok(r.size() as u32)
+}
+
+/// Ensure a range of registers can be read
+///
+/// Registers that don't exist inside the range are read as zero,
+/// but the first register must exist.
+/// Values spanning multiple registers must be read as a whole.
+fn check_read(regs: &[reg::Descriptor], start_addr: u16, quantity: u16) -> Result<(), StatusCode> {
+    let end = start_addr as u32 + quantity as u32;
+    if end > 0x10000 {
+        return Err(StatusCode::IllegalDataAddr);
+    }
+
+    let mut cursor = Cursor::new(regs, start_addr);
+    if cursor.seek(regs, start_addr).is_none() {
+        return Err(StatusCode::IllegalDataAddr);
+    }
+
+    let mut addr = start_addr as u32;
+    while addr < end {
+        match cursor.seek(regs, addr as u16).map(|ix| &regs[ix]) {
+            Some(r) => {
+                if !r.read_allowed() {
+                    return Err(StatusCode::IllegalDataAddr);
+                }
+                if r.check().is_err() {
+                    return Err(StatusCode::DeviceFail);
+                }
+                addr += span(r, addr, end)?;
+            }
+            None => addr += 1,
+        }
+    }
+
+    Ok(())
+}
+
+/// Read a range of registers into the response, including the byte count
+///
+/// The range must have been validated with [`check_read`] first.
+fn read_range(
+    regs: &[reg::Descriptor],
+    start_addr: u16,
+    quantity: u16,
+    res: &mut PDUBuf,
+) -> StatusCode {
+    let byte_count = quantity as usize * 2;
+    res.p[1] = byte_count as u8;
+    res.size = 2 + byte_count;
+
+    // Clear response buffer
+    res.p[2..res.size].fill(0);
+
+    let end = start_addr as u32 + quantity as u32;
+    let mut cursor = Cursor::new(regs, start_addr);
+    let mut addr = start_addr as u32;
+    while addr < end {
+        // If register doesn't exist, it's left as 0
+        let Some(r) = cursor.seek(regs, addr as u16).map(|ix| &regs[ix]) else {
+            addr += 1;
+            continue;
+        };
+
+        let n = match span(r, addr, end) {
+            Ok(n) => n,
+            Err(status_code) => return status_code,
+        };
+        let ix = 2 + (addr - start_addr as u32) as usize * 2;
+        match r.read_at_unchecked(addr as u16, &mut res.p[ix..ix + n as usize * 2]) {
+            Ok(()) => (),
+            Err(Error::ReadNotSuppported) => (), // Leave as 0
+            Err(Error::ReadLocked) => return StatusCode::IllegalDataAddr,
+            Err(..) => return StatusCode::DeviceFail,
+        }
+        addr += n;
+    }
+
+    StatusCode::Ok
+}
+
+/// Ensure all registers in a range exist and can be written to with the request data
+///
+/// Values spanning multiple registers must be written as a whole.
+fn check_write(regs: &[reg::Descriptor], start_addr: u16, data: &[u8]) -> Result<(), StatusCode> {
+    let end = start_addr as u32 + (data.len() / 2) as u32;
+    if end > 0x10000 {
+        return Err(StatusCode::IllegalDataAddr);
+    }
+
+    let mut cursor = Cursor::new(regs, start_addr);
+    let mut addr = start_addr as u32;
+    while addr < end {
+        match cursor.seek(regs, addr as u16).map(|ix| &regs[ix]) {
+            Some(r) => {
+                if r.write.is_none() || !r.write_allowed() {
+                    return Err(StatusCode::IllegalDataAddr);
+                }
+                if r.check().is_err() {
+                    return Err(StatusCode::DeviceFail);
+                }
+
+                let n = span(r, addr, end)?;
+                let ix = (addr - start_addr as u32) as usize * 2;
+                match r.validate_at_unchecked(addr as u16, &data[ix..ix + n as usize * 2]) {
+                    Ok(()) => (),
+                    Err(Error::InvalidValue) => return Err(StatusCode::IllegalDataValue),
+                    Err(Error::Rejected(status_code)) => return Err(status_code),
+                    Err(..) => return Err(StatusCode::IllegalDataAddr),
+                }
+                addr += n;
+            }
+            None => return Err(StatusCode::IllegalDataAddr),
+        }
+    }
 
-pub fn read_multiple(buf: &[u8], res: &mut PDUBuf) -> StatusCode {
+    Ok(())
+}
+
+/// Write a range of registers from request data
+///
+/// The range must have been validated with [`check_write`] first.
+fn write_range(regs: &mut [reg::Descriptor], start_addr: u16, data: &[u8]) -> StatusCode {
+    let end = start_addr as u32 + (data.len() / 2) as u32;
+    let mut cursor = Cursor::new(regs, start_addr);
+    let mut addr = start_addr as u32;
+    while addr < end {
+        match cursor.seek(regs, addr as u16).map(|ix| &mut regs[ix]) {
+            Some(r) => {
+                let n = match span(r, addr, end) {
+                    Ok(n) => n,
+                    Err(..) => return StatusCode::DeviceFail,
+                };
+                let ix = (addr - start_addr as u32) as usize * 2;
+                match r.write_at_unchecked(addr as u16, &data[ix..ix + n as usize * 2]) {
+                    Ok(()) => (),
+                    Err(..) => return StatusCode::DeviceFail,
+                };
+                addr += n;
+            }
+            None => return StatusCode::DeviceFail,
+        };
+    }
+
+    StatusCode::Ok
+}
+
+/// List the changes writing a range of registers would make
+///
+/// The range must have been validated with [`check_write`] first.
+fn stage(regs: &[reg::Descriptor], start_addr: u16, data: &[u8]) -> Vec<reg::Change> {
+    let mut changes = Vec::with_capacity(data.len() / 2);
+
+    let end = start_addr as u32 + (data.len() / 2) as u32;
+    let mut cursor = Cursor::new(regs, start_addr);
+    let mut addr = start_addr as u32;
+    while addr < end {
+        let Some(r) = cursor.seek(regs, addr as u16).map(|ix| &regs[ix]) else {
+            break;
+        };
+        let Ok(n) = span(r, addr, end) else {
+            break;
+        };
+
+        let ix = (addr - start_addr as u32) as usize * 2;
+        let mut old = vec![0; n as usize * 2];
+        let readable = r.read_at_unchecked(addr as u16, &mut old).is_ok();
+
+        for i in 0..n as usize {
+            changes.push(reg::Change {
+                address: addr as u16 + i as u16,
+                old: readable.then(|| BigEndian::read_u16(&old[i * 2..])),
+                new: BigEndian::read_u16(&data[ix + i * 2..]),
+            });
+        }
+        addr += n;
+    }
+
+    changes
+}
+
+/// Validate a range of registers, pass the changes to the commit hook and write them
+fn commit_write(inst: &mut Instance, start_addr: u16, data: &[u8]) -> StatusCode {
+    let regs = match inst.holding_regs.as_deref_mut() {
+        Some(r) => r,
+        None => return StatusCode::DeviceFail,
+    };
+
+    if let Err(status_code) = check_write(regs, start_addr, data) {
+        return status_code;
+    }
+
+    if let Some(cb) = &mut inst.commit_reg_write {
+        if cb(&stage(regs, start_addr, data)).is_err() {
+            return StatusCode::DeviceFail;
+        }
+    }
+
+    write_range(regs, start_addr, data)
+}
+
+pub fn read_multiple(
+    inst: &Instance,
+    buf: &[u8],
+    res: &mut PDUBuf,
+    read_input_regs: bool,
+) -> Result<StatusCode, ()> {
     let [fc, addr_hi, addr_lo, q_hi, q_lo] = match <[u8; 5]>::try_from(buf) {
         Ok(v) => v,
-        Err(..) => return StatusCode::IllegalDataValue,
+        Err(..) => return Ok(StatusCode::IllegalDataValue),
+    };
+
+    let regs = if read_input_regs {
+        match inst.input_regs {
+            Some(r) => r,
+            None => return Err(()),
+        }
+    } else {
+        match inst.holding_regs.as_deref() {
+            Some(r) => r,
+            None => return Err(()),
+        }
     };
 
     if fc != FunctionCode::ReadInputRegs as u8 && fc != FunctionCode::ReadHoldingRegs as u8 {
-        return StatusCode::DeviceFail;
+        return Ok(StatusCode::DeviceFail);
     }
 
-    let _start_addr = u16::from_be_bytes([addr_hi, addr_lo]);
+    let start_addr = u16::from_be_bytes([addr_hi, addr_lo]);
     let quantity = u16::from_be_bytes([q_hi, q_lo]);
 
-    if quantity == 0 || quantity > 125 {
-        // MBREG_N_READ_MAX
-        return StatusCode::IllegalDataValue;
+    // Validate quantity
+    if quantity == 0 || quantity > MAX_READ_QUANTITY {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    if let Err(status_code) = check_read(regs, start_addr, quantity) {
+        return Ok(status_code);
+    }
+
+    Ok(read_range(regs, start_addr, quantity, res))
+}
+
+pub fn write_single(inst: &mut Instance, buf: &[u8], res: &mut PDUBuf) -> Result<StatusCode, ()> {
+    if inst.holding_regs.is_none() {
+        return Err(());
+    }
+
+    let [fc, addr_hi, addr_lo, v_hi, v_lo] = match <[u8; 5]>::try_from(buf) {
+        Ok(v) => v,
+        Err(..) => return Ok(StatusCode::IllegalDataValue),
+    };
+
+    if fc != FunctionCode::WriteSingleReg as u8 {
+        return Ok(StatusCode::DeviceFail);
+    }
+
+    let addr = u16::from_be_bytes([addr_hi, addr_lo]);
+
+    match commit_write(inst, addr, &[v_hi, v_lo]) {
+        StatusCode::Ok => (),
+        status_code => return Ok(status_code),
+    };
+
+    BigEndian::write_u16(&mut res.p[1..], addr);
+    res.p[3] = v_hi;
+    res.p[4] = v_lo;
+    res.size = 5;
+
+    Ok(StatusCode::Ok)
+}
+
+pub fn write_multiple(inst: &mut Instance, buf: &[u8], res: &mut PDUBuf) -> Result<StatusCode, ()> {
+    if inst.holding_regs.is_none() {
+        return Err(());
+    }
+
+    // Check that request data is at least big enough for fields
+    // 'fc', 'addr', 'nr of regs', 'byte count' and 'data'... (u8 + u16 + u16 + u8 + ...)
+    if buf.len() < 8 {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    if buf[0] != FunctionCode::WriteMultipleRegs as u8 {
+        return Ok(StatusCode::DeviceFail);
+    }
+
+    let start_addr = BigEndian::read_u16(&buf[1..]);
+    let quantity = BigEndian::read_u16(&buf[3..]);
+    let byte_count = buf[5];
+
+    if quantity == 0 || quantity > MAX_WRITE_QUANTITY {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    if byte_count as u16 != quantity * 2 {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    if buf.len() != 6 + byte_count as usize {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    match commit_write(inst, start_addr, &buf[6..]) {
+        StatusCode::Ok => (),
+        status_code => return Ok(status_code),
+    };
+
+    BigEndian::write_u16(&mut res.p[1..], start_addr);
+    BigEndian::write_u16(&mut res.p[3..], quantity);
+    res.size = 5;
+
+    Ok(StatusCode::Ok)
+}
+
+pub fn mask_write(inst: &mut Instance, buf: &[u8], res: &mut PDUBuf) -> Result<StatusCode, ()> {
+    let regs = match inst.holding_regs.as_deref_mut() {
+        Some(r) => r,
+        None => return Err(()),
+    };
+
+    if buf.len() != 7 {
+        return Ok(StatusCode::IllegalDataValue);
     }
 
-    res.p[1] = (2 * quantity) as u8; // Byte count
-    res.size = 2;
+    if buf[0] != FunctionCode::MaskWriteReg as u8 {
+        return Ok(StatusCode::DeviceFail);
+    }
 
-    // TODO: Do this properly
+    let addr = BigEndian::read_u16(&buf[1..]);
+    let and_mask = BigEndian::read_u16(&buf[3..]);
+    let or_mask = BigEndian::read_u16(&buf[5..]);
 
-    for i in 0..quantity {
-        let j = (2 + i * 2) as usize;
-        if i % 2 == 0 {
-            res.p[j] = 0xDE;
-            res.p[j + 1] = 0xAD;
-        } else {
-            res.p[j] = 0xBE;
-            res.p[j + 1] = 0xEF;
+    // The new value goes through the same staging as other writes
+    let current = match reg::find(addr, regs) {
+        Some(r) if r.write.is_none() || !r.write_allowed() => {
+            return Ok(StatusCode::IllegalDataAddr)
         }
-        res.size += 2;
+        Some(r) => {
+            let mut buf = [0; 2];
+            match r.read_at(addr, &mut buf) {
+                Ok(()) => u16::from_be_bytes(buf),
+                Err(..) => return Ok(StatusCode::IllegalDataAddr),
+            }
+        }
+        None => return Ok(StatusCode::IllegalDataAddr),
+    };
+
+    let value = (current & and_mask) | (or_mask & !and_mask);
+    match commit_write(inst, addr, &value.to_be_bytes()) {
+        StatusCode::Ok => (),
+        status_code => return Ok(status_code),
+    };
+
+    res.p[1..7].copy_from_slice(&buf[1..7]);
+    res.size = 7;
+
+    Ok(StatusCode::Ok)
+}
+
+pub fn read_write_multiple(
+    inst: &mut Instance,
+    buf: &[u8],
+    res: &mut PDUBuf,
+) -> Result<StatusCode, ()> {
+    let regs = match inst.holding_regs.as_deref_mut() {
+        Some(r) => r,
+        None => return Err(()),
+    };
+
+    // Check that request data is at least big enough for fields
+    // 'fc', 'read addr', 'nr of read regs', 'write addr', 'nr of write regs',
+    // 'byte count' and 'data'... (u8 + u16 + u16 + u16 + u16 + u8 + ...)
+    if buf.len() < 12 {
+        return Ok(StatusCode::IllegalDataValue);
     }
 
-    StatusCode::Ok
+    if buf[0] != FunctionCode::ReadWriteRegs as u8 {
+        return Ok(StatusCode::DeviceFail);
+    }
+
+    let read_addr = BigEndian::read_u16(&buf[1..]);
+    let read_quantity = BigEndian::read_u16(&buf[3..]);
+    let write_addr = BigEndian::read_u16(&buf[5..]);
+    let write_quantity = BigEndian::read_u16(&buf[7..]);
+    let byte_count = buf[9];
+
+    if read_quantity == 0 || read_quantity > MAX_READ_QUANTITY {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    if write_quantity == 0 || write_quantity > MAX_RW_WRITE_QUANTITY {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    if byte_count as u16 != write_quantity * 2 {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    if buf.len() != 10 + byte_count as usize {
+        return Ok(StatusCode::IllegalDataValue);
+    }
+
+    // Validate both ranges before touching anything
+    if let Err(status_code) = check_read(regs, read_addr, read_quantity) {
+        return Ok(status_code);
+    }
+    if let Err(status_code) = check_write(regs, write_addr, &buf[10..]) {
+        return Ok(status_code);
+    }
+
+    // The write operation is performed before the read
+    match commit_write(inst, write_addr, &buf[10..]) {
+        StatusCode::Ok => (),
+        status_code => return Ok(status_code),
+    };
+
+    match inst.holding_regs.as_deref() {
+        Some(regs) => Ok(read_range(regs, read_addr, read_quantity, res)),
+        None => Ok(StatusCode::DeviceFail),
+    }
 }
@@ -1,44 +1,141 @@
 pub mod adu;
+pub mod adu_ascii;
 pub mod adu_tcp;
+pub mod builder;
 pub mod coil;
 pub mod crc;
 mod def;
 mod func;
+pub mod map;
 pub mod pdu;
+pub mod reg;
+pub mod rtu;
+#[cfg(all(feature = "serial", unix))]
+pub mod serial;
 
-pub use crate::def::{FunctionCode, StatusCode};
-use crate::pdu::PDUBuf;
+use std::collections::BTreeMap;
 
+pub use crate::builder::{BuildError, InstanceBuilder};
+pub use crate::def::{Counters, FunctionCode, Outcome, Reason, StatusCode};
+pub use crate::map::ModbusMap;
+use crate::pdu::{FunctionHandler, Middleware, PDUBuf};
+#[cfg(feature = "derive")]
+pub use mbrs_derive::ModbusMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+/// Serial slave configuration
+///
+/// The line settings are only used when the library opens the port itself, see `serial::Server`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SerialConfig {
     pub slave_addr: u8,
+    pub baud: u32,
+    pub parity: Parity,
+    /// RTU requires 8, ASCII usually uses 7
+    pub data_bits: u8,
+    /// 1 or 2
+    pub stop_bits: u8,
+}
+
+impl SerialConfig {
+    /// Configuration with the default line settings of the spec, 19200 baud 8E1
+    pub fn new(slave_addr: u8) -> Self {
+        Self {
+            slave_addr,
+            baud: 19200,
+            parity: Parity::Even,
+            data_bits: 8,
+            stop_bits: 1,
+        }
+    }
 }
 
-pub struct Instance<'a> {
-    pub disc_inputs: Option<&'a [coil::Descriptor<'a>]>,
-    pub coils: Option<&'a [coil::Descriptor<'a>]>,
+/// User defined function code handler
+///
+/// Called with the request PDU for function codes the library doesn't handle itself.
+/// The response starts out holding only the function code.
+/// Return `Err(())` if the request isn't handled either,
+/// the library then responds with [`StatusCode::IllegalFc`].
+pub type HandleFn<'a> = dyn FnMut(&Instance, &[u8], &mut PDUBuf) -> Result<StatusCode, ()> + 'a;
+
+/// Commit hook for a staged write request
+///
+/// Called with every point the request is about to change, before anything is written.
+/// Return `Err(())` to veto the whole request,
+/// nothing is then written and the library responds with [`StatusCode::DeviceFail`].
+pub type CommitFn<'a, T> = dyn FnMut(&[T]) -> Result<(), ()> + 'a;
+
+/// A Modbus slave instance
+///
+/// `'a` is the lifetime of the point tables, `'b` the lifetime of the data they point into.
+#[derive(Default)]
+pub struct Instance<'a, 'b> {
+    pub disc_inputs: Option<&'a [coil::Descriptor<'b>]>,
+    pub coils: Option<&'a mut [coil::Descriptor<'b>]>,
+
+    pub input_regs: Option<&'a [reg::Descriptor<'b>]>,
+    pub holding_regs: Option<&'a mut [reg::Descriptor<'b>]>,
+
+    /// Handlers for single function codes, see [`Instance::register_handler`]
+    pub handlers: BTreeMap<u8, Box<dyn FunctionHandler + 'b>>,
 
-    pub handle_fn: Option<Box<dyn FnMut(&Instance, &[u8], &mut PDUBuf) -> StatusCode + 'a>>,
+    pub handle_fn: Option<Box<HandleFn<'b>>>,
 
-    pub commit_coil_write: Option<Box<dyn FnMut() + 'a>>,
+    /// Hooks run around every request, see [`Instance::add_middleware`]
+    pub middleware: Vec<Box<dyn Middleware + 'b>>,
+
+    /// Called once after all coils of a write request have been written (FC 05 and 15)
+    pub commit_coil_write: Option<Box<dyn FnMut() + 'b>>,
+    /// Called once with all coils of a write request before they are written (FC 05 and 15)
+    pub stage_coil_write: Option<Box<CommitFn<'b, coil::Change>>>,
+    /// Called once with all registers of a write request before they are written (FC 06, 16, 22 and 23)
+    pub commit_reg_write: Option<Box<CommitFn<'b, reg::Change>>>,
 
     pub serial: Option<SerialConfig>,
+
+    /// Communication counters, kept up to date as requests are handled
+    ///
+    /// Also read through FC 08 Diagnostics, see [`Instance::clear_counters`].
+    pub counters: Counters,
+    /// Set by FC 08 Force Listen Only Mode, no requests are answered until communications are restarted
+    ///
+    /// Requests are still counted while listening only.
+    pub listen_only: bool,
 }
 
-impl<'a> Default for Instance<'a> {
-    fn default() -> Self {
-        Self {
-            disc_inputs: Default::default(),
-            coils: Default::default(),
-            handle_fn: Default::default(),
-            commit_coil_write: Default::default(),
-            serial: Default::default(),
-        }
+impl<'a, 'b> Instance<'a, 'b> {
+    /// Start building an instance, see [`InstanceBuilder`]
+    pub fn builder() -> InstanceBuilder<'a, 'b> {
+        InstanceBuilder::new()
     }
-}
 
-impl<'a> Instance<'a> {
+    /// Reset the internal state, clearing the counters and leaving listen only mode
     pub fn init(&mut self) {
-        // TODO: Initialize internla state
+        self.clear_counters();
+        self.listen_only = false;
+    }
+
+    /// Reset all communication counters to zero
+    pub fn clear_counters(&mut self) {
+        self.counters = Counters::default();
+    }
+
+    /// Register a handler for a function code, replacing any previously registered one
+    ///
+    /// The handler is consulted before the built-in handlers and [`Instance::handle_fn`].
+    pub fn register_handler(&mut self, fc: u8, handler: impl FunctionHandler + 'b) {
+        self.handlers.insert(fc, Box::new(handler));
+    }
+
+    /// Add middleware to run around every request, after any previously added
+    pub fn add_middleware(&mut self, middleware: impl Middleware + 'b) {
+        self.middleware.push(Box::new(middleware));
     }
 }
 
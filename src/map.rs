@@ -0,0 +1,97 @@
+use crate::{coil, reg, Instance};
+
+/// Point tables exposing the fields of a struct, see [`ModbusMap`]
+///
+/// The tables are sorted by address.
+#[derive(Default)]
+pub struct Map<'b> {
+    pub disc_inputs: Vec<coil::Descriptor<'b>>,
+    pub coils: Vec<coil::Descriptor<'b>>,
+    pub input_regs: Vec<reg::Descriptor<'b>>,
+    pub holding_regs: Vec<reg::Descriptor<'b>>,
+}
+
+impl<'b> Map<'b> {
+    /// Create an instance serving the tables of this map
+    ///
+    /// Empty tables are left out.
+    pub fn instance(&mut self) -> Instance<'_, 'b> {
+        Instance {
+            disc_inputs: (!self.disc_inputs.is_empty()).then_some(&self.disc_inputs[..]),
+            coils: (!self.coils.is_empty()).then_some(&mut self.coils[..]),
+            input_regs: (!self.input_regs.is_empty()).then_some(&self.input_regs[..]),
+            holding_regs: (!self.holding_regs.is_empty()).then_some(&mut self.holding_regs[..]),
+            ..Default::default()
+        }
+    }
+}
+
+/// A struct whose fields are exposed as Modbus points
+///
+/// Usually implemented with `#[derive(ModbusMap)]`, which requires the `derive` feature.
+/// Fields are marked with `#[coil(address)]`, `#[discrete(address)]`,
+/// `#[holding(address, ..)]` or `#[input(address, ..)]`.
+/// Register fields take the options `order = "CDAB"` and `scale`, `offset`, `signed`, `min`
+/// and `max`, see [`reg::Order`] and [`reg::Scale`].
+/// Overlapping points are rejected at compile time.
+///
+/// # Examples
+///
+/// ```
+/// use mbrs::map::ModbusMap;
+/// use mbrs_derive::ModbusMap;
+///
+/// #[derive(Default, ModbusMap)]
+/// struct Pump {
+///     #[coil(0)]
+///     enabled: bool,
+///     #[holding(0)]
+///     total: u32,
+///     #[holding(2)]
+///     setpoint: u16,
+/// }
+///
+/// let mut pump = Pump::default();
+/// assert_eq!(pump.modbus_map().holding_regs.len(), 2);
+/// ```
+///
+/// Two points can't share an address:
+///
+/// ```compile_fail
+/// # use mbrs_derive::ModbusMap;
+/// #[derive(ModbusMap)]
+/// struct Pump {
+///     #[coil(1)]
+///     enabled: bool,
+///     #[coil(1)]
+///     running: bool,
+/// }
+/// ```
+///
+/// A value spanning several registers can't overlap the next point:
+///
+/// ```compile_fail,E0080
+/// # use mbrs_derive::ModbusMap;
+/// #[derive(ModbusMap)]
+/// struct Pump {
+///     #[holding(0)]
+///     total: u32,
+///     #[holding(1)]
+///     setpoint: u16,
+/// }
+/// ```
+///
+/// Nor span past the last address:
+///
+/// ```compile_fail,E0080
+/// # use mbrs_derive::ModbusMap;
+/// #[derive(ModbusMap)]
+/// struct Pump {
+///     #[holding(0xFFFF)]
+///     total: u32,
+/// }
+/// ```
+pub trait ModbusMap {
+    /// Map the fields of this struct, the map borrows them until it is dropped
+    fn modbus_map(&mut self) -> Map<'_>;
+}
@@ -1,4 +1,7 @@
-use crate::def::{self, FunctionCode, StatusCode};
+use std::net::SocketAddr;
+
+use crate::adu::SLAVE_ADDR_BROADCAST;
+use crate::def::{self, Counters, FunctionCode, Outcome, Reason, StatusCode};
 use crate::func;
 use crate::Instance;
 
@@ -10,73 +13,257 @@ pub struct PDUBuf<'a> {
     pub size: usize,
 }
 
-fn handle_fn<'a>(inst: &'a Instance<'a>, buf: &[u8], res: &mut PDUBuf) -> StatusCode {
+/// Handler for a single function code, see [`Instance::register_handler`]
+///
+/// Registered handlers are consulted before the built-in ones,
+/// so they can be used to add or override function codes.
+pub trait FunctionHandler {
+    /// Handle a request PDU
+    ///
+    /// The response starts out holding only the function code.
+    /// Return `Err(())` to leave the request to the built-in handlers.
+    #[allow(clippy::result_unit_err)]
+    fn handle(
+        &mut self,
+        inst: &mut Instance,
+        buf: &[u8],
+        res: &mut PDUBuf,
+    ) -> Result<StatusCode, ()>;
+}
+
+impl<F> FunctionHandler for F
+where
+    F: FnMut(&mut Instance, &[u8], &mut PDUBuf) -> Result<StatusCode, ()>,
+{
+    fn handle(
+        &mut self,
+        inst: &mut Instance,
+        buf: &[u8],
+        res: &mut PDUBuf,
+    ) -> Result<StatusCode, ()> {
+        self(inst, buf, res)
+    }
+}
+
+/// Transport a request was received on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// The PDU was passed directly to [`handle_req`]
+    Local,
+    /// Modbus serial, with the slave address the request was sent to
+    Serial { slave_addr: u8 },
+    /// Modbus TCP, with the unit id of the request and the peer if known
+    Tcp {
+        unit_id: u8,
+        peer: Option<SocketAddr>,
+    },
+}
+
+/// Hooks run around every request, see [`Instance::add_middleware`]
+///
+/// Middleware runs in the order it was added.
+pub trait Middleware {
+    /// Called before the request is handled
+    ///
+    /// `req` holds a copy of the request PDU, starting with the function code,
+    /// and may be rewritten. Setting its size to zero drops the request without a response.
+    /// Return an exception code to reject the request,
+    /// the remaining middleware is then skipped.
+    fn before_request(
+        &mut self,
+        _transport: &Transport,
+        _req: &mut PDUBuf,
+    ) -> Result<(), StatusCode> {
+        Ok(())
+    }
+
+    /// Called with the request as handled and the response PDU, including exception responses
+    fn after_response(&mut self, _transport: &Transport, _req: &[u8], _res: &[u8]) {}
+}
+
+fn handle_fn(inst: &mut Instance, buf: &[u8], res: &mut PDUBuf) -> StatusCode {
+    // Registered handlers take precedence over the built-in ones.
+    // The handler is taken out of the instance while running, so it can be given access to the instance.
+    if let Some(mut handler) = inst.handlers.remove(&buf[0]) {
+        let status_code = handler.handle(inst, buf, res);
+        inst.handlers.insert(buf[0], handler);
+
+        if let Ok(status_code) = status_code {
+            return status_code;
+        }
+
+        // Reset the response in case the handler left something behind
+        res.p[0] = buf[0];
+        res.size = 1;
+    }
+
     match FunctionCode::try_from(buf[0]) {
-        Ok(FunctionCode::ReadCoils) => match func::coils::read_multiple(inst, buf, res, false) {
-            Ok(status_code) => return status_code,
-            Err(()) => (),
-        },
+        Ok(FunctionCode::ReadCoils) => {
+            if let Ok(status_code) = func::coils::read_multiple(inst, buf, res, false) {
+                return status_code;
+            }
+        }
         Ok(FunctionCode::ReadDiscreteInputs) => {
-            match func::coils::read_multiple(inst, buf, res, true) {
-                Ok(status_code) => return status_code,
-                Err(()) => (),
+            if let Ok(status_code) = func::coils::read_multiple(inst, buf, res, true) {
+                return status_code;
+            }
+        }
+        Ok(FunctionCode::ReadHoldingRegs) => {
+            if let Ok(status_code) = func::regs::read_multiple(inst, buf, res, false) {
+                return status_code;
+            }
+        }
+        Ok(FunctionCode::ReadInputRegs) => {
+            if let Ok(status_code) = func::regs::read_multiple(inst, buf, res, true) {
+                return status_code;
+            }
+        }
+        Ok(FunctionCode::WriteSingleCoil) => {
+            if let Ok(status_code) = func::coils::write_single(inst, buf, res) {
+                return status_code;
+            }
+        }
+        Ok(FunctionCode::WriteSingleReg) => {
+            if let Ok(status_code) = func::regs::write_single(inst, buf, res) {
+                return status_code;
             }
         }
-        Ok(FunctionCode::ReadHoldingRegs) => return func::regs::read_multiple(buf, res),
-        Ok(FunctionCode::ReadInputRegs) => return func::regs::read_multiple(buf, res),
-        Ok(FunctionCode::WriteSingleCoil) => match func::coils::write_single(inst, buf, res) {
-            Ok(status_code) => return status_code,
-            Err(()) => (),
-        },
-        Ok(FunctionCode::WriteSingleReg) => (),
         Ok(FunctionCode::ReadExceptionStatus) => (),
-        Ok(FunctionCode::Diagnostics) => (),
+        Ok(FunctionCode::Diagnostics) => {
+            if let Ok(status_code) = func::diag::handle(inst, buf, res) {
+                return status_code;
+            }
+        }
         Ok(FunctionCode::CommEventCounter) => (),
         Ok(FunctionCode::CommEventLog) => (),
-        Ok(FunctionCode::WriteMultipleCoils) => match func::coils::write_multiple(inst, buf, res) {
-            Ok(status_code) => return status_code,
-            Err(()) => (),
-        },
-        Ok(FunctionCode::WriteMultipleRegs) => (),
+        Ok(FunctionCode::WriteMultipleCoils) => {
+            if let Ok(status_code) = func::coils::write_multiple(inst, buf, res) {
+                return status_code;
+            }
+        }
+        Ok(FunctionCode::WriteMultipleRegs) => {
+            if let Ok(status_code) = func::regs::write_multiple(inst, buf, res) {
+                return status_code;
+            }
+        }
         Ok(FunctionCode::ReportSlaveId) => (), // Should be implemented through Instance::handle_fn
         Ok(FunctionCode::ReadFileRecord) => (),
         Ok(FunctionCode::WriteFileRecord) => (),
-        Ok(FunctionCode::MaskWriteReg) => (),
-        Ok(FunctionCode::ReadWriteRegs) => (),
+        Ok(FunctionCode::MaskWriteReg) => {
+            if let Ok(status_code) = func::regs::mask_write(inst, buf, res) {
+                return status_code;
+            }
+        }
+        Ok(FunctionCode::ReadWriteRegs) => {
+            if let Ok(status_code) = func::regs::read_write_multiple(inst, buf, res) {
+                return status_code;
+            }
+        }
         Ok(FunctionCode::ReadFifoQueue) => (),
         Err(()) => (),
     };
 
     // If the library was not able to handle this request,
     // call the user defined hanlder function if present.
-    /*if let Some(f) = &inst.handle_fn {
-        f(inst, buf, res)
-    } else {
-        StatusCode::IllegalFc
-    }*/
+    // It's taken out of the instance while running, so it can be given access to the instance.
+    if let Some(mut f) = inst.handle_fn.take() {
+        let status_code = f(inst, buf, res);
+        inst.handle_fn = Some(f);
+
+        if let Ok(status_code) = status_code {
+            return status_code;
+        }
+    }
 
     StatusCode::IllegalFc
 }
 
-pub fn handle_req<'a>(inst: &'a Instance<'a>, buf: &[u8], res: &mut [u8; SIZE_MAX]) -> usize {
-    let fc = match buf.get(0) {
-        Some(&b) => b,
-        None => return 0,
+pub fn handle_req(inst: &mut Instance, buf: &[u8], res: &mut [u8; SIZE_MAX]) -> Outcome {
+    handle_req_with(inst, &Transport::Local, buf, res)
+}
+
+/// Handle a request PDU received on `transport`
+pub fn handle_req_with(
+    inst: &mut Instance,
+    transport: &Transport,
+    buf: &[u8],
+    res: &mut [u8; SIZE_MAX],
+) -> Outcome {
+    if buf.is_empty() {
+        return Outcome::NoResponse(Reason::Undersized);
+    }
+    if buf.len() > SIZE_MAX {
+        return Outcome::NoResponse(Reason::Oversized);
+    }
+
+    // In listen only mode only a restart of communications is acted upon, and never answered
+    let listen_only = inst.listen_only;
+    if listen_only && !func::diag::is_restart(buf) {
+        return Outcome::NoResponse(Reason::ListenOnly);
+    }
+
+    // Copy the request so middleware can rewrite it
+    let mut req = [0; SIZE_MAX];
+    req[..buf.len()].copy_from_slice(buf);
+    let mut req = PDUBuf {
+        p: &mut req,
+        size: buf.len(),
     };
 
+    let mut status = StatusCode::Ok;
+    for m in inst.middleware.iter_mut() {
+        if let Err(status_code) = m.before_request(transport, &mut req) {
+            status = status_code;
+            break;
+        }
+    }
+
+    if req.size == 0 || req.size > SIZE_MAX {
+        return Outcome::NoResponse(Reason::Dropped);
+    }
+    let buf = &req.p[..req.size];
+
     let mut res = PDUBuf { p: res, size: 0 };
 
-    res.p[0] = fc;
+    res.p[0] = buf[0];
     res.size = 1;
 
-    match handle_fn(inst, buf, &mut res) {
+    if status == StatusCode::Ok {
+        status = handle_fn(inst, buf, &mut res);
+    }
+
+    if listen_only || inst.listen_only {
+        return Outcome::NoResponse(Reason::ListenOnly);
+    }
+
+    match status {
         StatusCode::Ok => (),
         status => {
+            // Broadcasts are never answered, so no exception is returned for them
+            let broadcast = matches!(
+                transport,
+                Transport::Serial {
+                    slave_addr: SLAVE_ADDR_BROADCAST
+                }
+            );
+            if !broadcast {
+                Counters::inc(&mut inst.counters.exceptions);
+                match status {
+                    StatusCode::NegaticeAcknowlage => Counters::inc(&mut inst.counters.naks),
+                    StatusCode::Busy => Counters::inc(&mut inst.counters.busy),
+                    _ => (),
+                }
+            }
+
             res.p[0] |= def::ERR_FLAG;
             res.p[1] = status as u8;
             res.size = 2;
         }
     };
 
-    res.size
+    for m in inst.middleware.iter_mut() {
+        m.after_response(transport, buf, &res.p[..res.size]);
+    }
+
+    Outcome::Response(res.size)
 }
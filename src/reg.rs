@@ -0,0 +1,784 @@
+use std::fmt::Display;
+
+use crate::StatusCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    ReadNotSuppported,
+    WriteNotSuppported,
+    ReadLocked,
+    WriteLocked,
+    /// The access doesn't cover the full register span of the value,
+    /// or the read and write methods of the descriptor span a different number of registers
+    SpanMismatch,
+    /// The descriptor spans no registers, or more than 65535
+    InvalidSize,
+    /// The written value is not accepted
+    InvalidValue,
+    /// The written value was rejected by the validator
+    Rejected(StatusCode),
+}
+
+/// Order of the bytes of a value spanning one or more registers
+///
+/// Named after how the 32-bit value `0xAABBCCDD` is laid out on the wire.
+/// Values of other sizes follow the same word and byte swapping rules.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// Big endian
+    #[default]
+    Abcd,
+    /// Big endian bytes, little endian words
+    Cdab,
+    /// Little endian bytes, big endian words
+    Badc,
+    /// Little endian
+    Dcba,
+}
+
+impl Order {
+    /// Rearrange big endian bytes into this order
+    ///
+    /// Swapping is its own inverse, so this also rearranges bytes in this order back into big endian.
+    fn apply(self, bytes: &mut [u8]) {
+        if matches!(self, Order::Cdab | Order::Dcba) {
+            let n_words = bytes.len() / 2;
+            for i in 0..n_words / 2 {
+                let j = n_words - 1 - i;
+                bytes.swap(i * 2, j * 2);
+                bytes.swap(i * 2 + 1, j * 2 + 1);
+            }
+        }
+
+        if matches!(self, Order::Badc | Order::Dcba) {
+            for word in bytes.chunks_exact_mut(2) {
+                word.swap(0, 1);
+            }
+        }
+    }
+}
+
+/// A value type that can be stored in one or more consecutive registers
+pub trait Value: Copy {
+    /// Number of registers spanned by the value
+    const SIZE: u16;
+
+    /// Write the value as big endian bytes, `buf` must be `2 * SIZE` bytes long
+    fn write_be(self, buf: &mut [u8]);
+
+    /// Read the value from big endian bytes, `buf` must be `2 * SIZE` bytes long
+    fn read_be(buf: &[u8]) -> Self;
+
+    /// Read method reading a value of this type from `source`
+    fn read_method(source: Source<'_, Self>) -> ReadMethod<'_>;
+
+    /// Write method writing a value of this type to `sink`
+    fn write_method(sink: Sink<'_, Self>) -> WriteMethod<'_>;
+}
+
+macro_rules! impl_value {
+    ($($t:ty => $read:path, $write:path);+ $(;)?) => {
+        $(
+            impl Value for $t {
+                const SIZE: u16 = (std::mem::size_of::<$t>() / 2) as u16;
+
+                fn write_be(self, buf: &mut [u8]) {
+                    buf.copy_from_slice(&self.to_be_bytes());
+                }
+
+                fn read_be(buf: &[u8]) -> Self {
+                    <$t>::from_be_bytes(buf.try_into().unwrap())
+                }
+
+                fn read_method(source: Source<'_, Self>) -> ReadMethod<'_> {
+                    $read(source)
+                }
+
+                fn write_method(sink: Sink<'_, Self>) -> WriteMethod<'_> {
+                    $write(sink)
+                }
+            }
+        )+
+    };
+}
+
+fn u16_read_method(source: Source<u16>) -> ReadMethod {
+    match source {
+        Source::Value(v) => ReadMethod::Value(v),
+        Source::Ref(r) => ReadMethod::Ref(r),
+        Source::Fn(f) => ReadMethod::Fn(f),
+    }
+}
+
+fn u16_write_method(sink: Sink<u16>) -> WriteMethod {
+    match sink {
+        Sink::Ref(r) => WriteMethod::Ref(r),
+        Sink::Fn(f) => WriteMethod::Fn(f),
+    }
+}
+
+impl_value!(
+    u16 => u16_read_method, u16_write_method;
+    u32 => ReadMethod::U32, WriteMethod::U32;
+    i32 => ReadMethod::I32, WriteMethod::I32;
+    f32 => ReadMethod::F32, WriteMethod::F32;
+    u64 => ReadMethod::U64, WriteMethod::U64;
+    i64 => ReadMethod::I64, WriteMethod::I64;
+    f64 => ReadMethod::F64, WriteMethod::F64;
+);
+
+pub enum Source<'a, T> {
+    Value(T),
+    Ref(&'a T),
+    Fn(Box<dyn Fn() -> T + 'a>),
+}
+
+impl<'a, T: Value> Source<'a, T> {
+    pub fn get(&self) -> T {
+        match self {
+            Source::Value(v) => *v,
+            Source::Ref(&r) => r,
+            Source::Fn(f) => f(),
+        }
+    }
+}
+
+pub enum Sink<'a, T> {
+    Ref(&'a mut T),
+    Fn(Box<dyn FnMut(T) + 'a>),
+}
+
+impl<'a, T: Value> Sink<'a, T> {
+    pub fn set(&mut self, value: T) {
+        match self {
+            Sink::Ref(r) => **r = value,
+            Sink::Fn(f) => f(value),
+        }
+    }
+}
+
+/// Padding used to fill the unused part of string and byte array fields
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Padding {
+    #[default]
+    Nul,
+    Space,
+}
+
+impl Padding {
+    fn byte(self) -> u8 {
+        match self {
+            Padding::Nul => b'\0',
+            Padding::Space => b' ',
+        }
+    }
+}
+
+/// ASCII text packed two characters per register
+pub enum StrSource<'a> {
+    Value(&'static str),
+    Ref(&'a str),
+    Fn(Box<dyn Fn() -> String + 'a>),
+}
+
+/// Receives ASCII text with the padding removed
+pub enum StrSink<'a> {
+    Ref(&'a mut String),
+    Fn(Box<dyn FnMut(&str) + 'a>),
+}
+
+/// Raw bytes packed two per register
+pub enum BytesSource<'a> {
+    Value(&'static [u8]),
+    Ref(&'a [u8]),
+    Fn(Box<dyn Fn() -> Vec<u8> + 'a>),
+}
+
+/// Write handler for raw bytes
+pub type BytesFn<'a> = dyn FnMut(&[u8]) + 'a;
+
+/// Receives the raw bytes of the full field
+pub enum BytesSink<'a> {
+    /// Bytes are copied into the slice, truncated if the field is longer than the slice
+    Ref(&'a mut [u8]),
+    Fn(Box<BytesFn<'a>>),
+}
+
+/// Decode ASCII text from `buf`, removing trailing padding
+fn decode_str(buf: &[u8], padding: Padding) -> Result<&str, Error> {
+    let len = buf
+        .iter()
+        .rposition(|&b| b != padding.byte() && b != b'\0')
+        .map_or(0, |ix| ix + 1);
+
+    match std::str::from_utf8(&buf[..len]) {
+        Ok(text) if text.is_ascii() => Ok(text),
+        _ => Err(Error::InvalidValue),
+    }
+}
+
+/// Copy `src` into `buf`, truncating it or filling the rest with padding
+fn pad(src: &[u8], padding: Padding, buf: &mut [u8]) {
+    let n = src.len().min(buf.len());
+    buf[..n].copy_from_slice(&src[..n]);
+    buf[n..].fill(padding.byte());
+}
+
+/// Linear mapping between an engineering value and a raw 16-bit register value
+///
+/// `value = raw * factor + offset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scale {
+    pub factor: f32,
+    pub offset: f32,
+    /// The raw register value is an `i16` rather than an `u16`
+    pub signed: bool,
+    /// Lowest engineering value accepted on write
+    pub min: Option<f32>,
+    /// Highest engineering value accepted on write
+    pub max: Option<f32>,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            offset: 0.0,
+            signed: false,
+            min: None,
+            max: None,
+        }
+    }
+}
+
+impl Scale {
+    /// Map an engineering value to a raw register value, saturating at the limits of the raw type
+    fn encode(self, value: f32) -> u16 {
+        let raw = ((value - self.offset) / self.factor).round();
+        if self.signed {
+            raw as i16 as u16
+        } else {
+            raw as u16
+        }
+    }
+
+    /// Map a raw register value to an engineering value, checking the limits
+    fn decode(self, raw: u16) -> Result<f32, Error> {
+        let raw = if self.signed {
+            raw as i16 as f32
+        } else {
+            raw as f32
+        };
+        let value = raw * self.factor + self.offset;
+
+        if self.min.is_some_and(|min| value < min) || self.max.is_some_and(|max| value > max) {
+            return Err(Error::InvalidValue);
+        }
+
+        Ok(value)
+    }
+}
+
+pub enum ReadMethod<'a> {
+    Value(u16),
+    Ref(&'a u16),
+    Fn(Box<dyn Fn() -> u16 + 'a>),
+    U32(Source<'a, u32>),
+    I32(Source<'a, i32>),
+    F32(Source<'a, f32>),
+    U64(Source<'a, u64>),
+    I64(Source<'a, i64>),
+    F64(Source<'a, f64>),
+    Str {
+        /// Number of registers in the field
+        size: u16,
+        source: StrSource<'a>,
+    },
+    Bytes {
+        /// Number of registers in the field
+        size: u16,
+        source: BytesSource<'a>,
+    },
+    /// Engineering value mapped into a single register, see [`Descriptor::scale`]
+    Scaled(Source<'a, f32>),
+    /// Block of consecutive registers starting at the descriptor address
+    ///
+    /// Unlike other values, blocks can be accessed partially.
+    /// The byte order, padding and scale of the descriptor don't apply to blocks.
+    Block(&'a [u16]),
+}
+
+impl<'a> ReadMethod<'a> {
+    /// Number of registers held by the method, which may not fit in the address space
+    fn len(&self) -> usize {
+        match self {
+            ReadMethod::Value(..)
+            | ReadMethod::Ref(..)
+            | ReadMethod::Fn(..)
+            | ReadMethod::Scaled(..) => u16::SIZE as usize,
+            ReadMethod::U32(..) => u32::SIZE as usize,
+            ReadMethod::I32(..) => i32::SIZE as usize,
+            ReadMethod::F32(..) => f32::SIZE as usize,
+            ReadMethod::U64(..) => u64::SIZE as usize,
+            ReadMethod::I64(..) => i64::SIZE as usize,
+            ReadMethod::F64(..) => f64::SIZE as usize,
+            ReadMethod::Str { size, .. } | ReadMethod::Bytes { size, .. } => *size as usize,
+            ReadMethod::Block(block) => block.len(),
+        }
+    }
+
+    /// Number of registers spanned by the value, saturating at 65535
+    pub fn size(&self) -> u16 {
+        u16::try_from(self.len()).unwrap_or(u16::MAX)
+    }
+
+    /// Read the value as big endian bytes
+    fn read(&self, buf: &mut [u8], padding: Padding, scale: Scale) {
+        match self {
+            ReadMethod::Value(v) => v.write_be(buf),
+            ReadMethod::Ref(&r) => r.write_be(buf),
+            ReadMethod::Fn(f) => f().write_be(buf),
+            ReadMethod::U32(src) => src.get().write_be(buf),
+            ReadMethod::I32(src) => src.get().write_be(buf),
+            ReadMethod::F32(src) => src.get().write_be(buf),
+            ReadMethod::U64(src) => src.get().write_be(buf),
+            ReadMethod::I64(src) => src.get().write_be(buf),
+            ReadMethod::F64(src) => src.get().write_be(buf),
+            ReadMethod::Str { source, .. } => match source {
+                StrSource::Value(v) => pad(v.as_bytes(), padding, buf),
+                StrSource::Ref(r) => pad(r.as_bytes(), padding, buf),
+                StrSource::Fn(f) => pad(f().as_bytes(), padding, buf),
+            },
+            ReadMethod::Bytes { source, .. } => match source {
+                BytesSource::Value(v) => pad(v, padding, buf),
+                BytesSource::Ref(r) => pad(r, padding, buf),
+                BytesSource::Fn(f) => pad(&f(), padding, buf),
+            },
+            ReadMethod::Scaled(src) => scale.encode(src.get()).write_be(buf),
+            ReadMethod::Block(block) => read_block(block, buf),
+        }
+    }
+}
+
+pub enum WriteMethod<'a> {
+    Ref(&'a mut u16),
+    Fn(Box<dyn FnMut(u16) + 'a>),
+    U32(Sink<'a, u32>),
+    I32(Sink<'a, i32>),
+    F32(Sink<'a, f32>),
+    U64(Sink<'a, u64>),
+    I64(Sink<'a, i64>),
+    F64(Sink<'a, f64>),
+    Str {
+        /// Number of registers in the field
+        size: u16,
+        sink: StrSink<'a>,
+    },
+    Bytes {
+        /// Number of registers in the field
+        size: u16,
+        sink: BytesSink<'a>,
+    },
+    /// Engineering value mapped from a single register, see [`Descriptor::scale`]
+    Scaled(Sink<'a, f32>),
+    /// Block of consecutive registers starting at the descriptor address, see [`ReadMethod::Block`]
+    ///
+    /// The block can also be read if the descriptor has no read method.
+    Block(&'a mut [u16]),
+}
+
+impl<'a> WriteMethod<'a> {
+    /// Number of registers held by the method, see [`ReadMethod::size`]
+    fn len(&self) -> usize {
+        match self {
+            WriteMethod::Ref(..) | WriteMethod::Fn(..) | WriteMethod::Scaled(..) => {
+                u16::SIZE as usize
+            }
+            WriteMethod::U32(..) => u32::SIZE as usize,
+            WriteMethod::I32(..) => i32::SIZE as usize,
+            WriteMethod::F32(..) => f32::SIZE as usize,
+            WriteMethod::U64(..) => u64::SIZE as usize,
+            WriteMethod::I64(..) => i64::SIZE as usize,
+            WriteMethod::F64(..) => f64::SIZE as usize,
+            WriteMethod::Str { size, .. } | WriteMethod::Bytes { size, .. } => *size as usize,
+            WriteMethod::Block(block) => block.len(),
+        }
+    }
+
+    /// Number of registers spanned by the value, saturating at 65535
+    pub fn size(&self) -> u16 {
+        u16::try_from(self.len()).unwrap_or(u16::MAX)
+    }
+
+    /// Check that big endian bytes hold a value that can be written
+    fn validate(&self, buf: &[u8], padding: Padding, scale: Scale) -> Result<(), Error> {
+        match self {
+            WriteMethod::Str { .. } => decode_str(buf, padding).map(|_| ()),
+            WriteMethod::Scaled(..) => scale.decode(u16::read_be(buf)).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the value from big endian bytes
+    fn write(&mut self, buf: &[u8], padding: Padding, scale: Scale) -> Result<(), Error> {
+        match self {
+            WriteMethod::Ref(r) => **r = u16::read_be(buf),
+            WriteMethod::Fn(f) => f(u16::read_be(buf)),
+            WriteMethod::U32(sink) => sink.set(u32::read_be(buf)),
+            WriteMethod::I32(sink) => sink.set(i32::read_be(buf)),
+            WriteMethod::F32(sink) => sink.set(f32::read_be(buf)),
+            WriteMethod::U64(sink) => sink.set(u64::read_be(buf)),
+            WriteMethod::I64(sink) => sink.set(i64::read_be(buf)),
+            WriteMethod::F64(sink) => sink.set(f64::read_be(buf)),
+            WriteMethod::Str { sink, .. } => {
+                let text = decode_str(buf, padding)?;
+                match sink {
+                    StrSink::Ref(r) => {
+                        r.clear();
+                        r.push_str(text);
+                    }
+                    StrSink::Fn(f) => f(text),
+                }
+            }
+            WriteMethod::Bytes { sink, .. } => match sink {
+                BytesSink::Ref(r) => {
+                    let n = buf.len().min(r.len());
+                    r[..n].copy_from_slice(&buf[..n]);
+                }
+                BytesSink::Fn(f) => f(buf),
+            },
+            WriteMethod::Scaled(sink) => sink.set(scale.decode(u16::read_be(buf))?),
+            WriteMethod::Block(block) => write_block(block, buf),
+        }
+
+        Ok(())
+    }
+}
+
+/// Copy registers into big endian bytes
+fn read_block(block: &[u16], buf: &mut [u8]) {
+    for (r, b) in block.iter().zip(buf.chunks_mut(2)) {
+        b.copy_from_slice(&r.to_be_bytes());
+    }
+}
+
+/// Copy big endian bytes into registers
+fn write_block(block: &mut [u16], buf: &[u8]) {
+    for (r, b) in block.iter_mut().zip(buf.chunks(2)) {
+        *r = u16::from_be_bytes([b[0], b[1]]);
+    }
+}
+
+/// A register about to be written, see [`crate::Instance::commit_reg_write`]
+///
+/// Values spanning multiple registers show up as one change per register, as they appear on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub address: u16,
+    /// Current value, `None` if the register can't be read
+    pub old: Option<u16>,
+    pub new: u16,
+}
+
+/// Checks a value before it is written
+///
+/// Called with the registers of the value in [`Order::Abcd`], regardless of the wire order.
+/// The returned status code is sent back as the exception response.
+pub type Validator<'a> = dyn Fn(&[u16]) -> Result<(), StatusCode> + 'a;
+
+#[derive(Default)]
+pub struct Descriptor<'a> {
+    pub address: u16,
+    pub read: Option<ReadMethod<'a>>,
+    pub write: Option<WriteMethod<'a>>,
+
+    /// Byte order of the value on the wire
+    ///
+    /// For strings and byte arrays, [`Order::Badc`] swaps the two bytes within each register.
+    pub order: Order,
+    /// Padding of string and byte array fields
+    pub padding: Padding,
+    /// Mapping of scaled engineering values
+    pub scale: Scale,
+
+    pub rlock: Option<Box<dyn Fn() -> bool + 'a>>,
+    pub wlock: Option<Box<dyn Fn() -> bool + 'a>>,
+
+    pub validator: Option<Box<Validator<'a>>>,
+
+    pub post_write: Option<Box<dyn FnMut() + 'a>>,
+}
+
+impl<'a> PartialEq for Descriptor<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.address == other.address
+    }
+}
+
+impl<'a> Eq for Descriptor<'a> {}
+
+impl<'a> PartialOrd for Descriptor<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for Descriptor<'a> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.address.cmp(&other.address)
+    }
+}
+
+impl<'a> Display for Descriptor<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{:04X}", self.address)
+    }
+}
+
+/// Finds the register spanning an address
+///
+/// This uses binary search, so all registers must be sorted in ascending order by address
+pub fn find<'a>(address: u16, regs: &'a [Descriptor<'a>]) -> Option<&'a Descriptor<'a>> {
+    let ix = regs
+        .partition_point(|reg| reg.address <= address)
+        .checked_sub(1)?;
+    match address <= regs[ix].end_address() {
+        true => Some(&regs[ix]),
+        false => None,
+    }
+}
+
+/// Finds the register spanning an address and get a mutable reference to it
+///
+/// This uses binary search, so all registers must be sorted in ascending order by address
+pub fn find_mut<'a, 'b>(
+    address: u16,
+    regs: &'a mut [Descriptor<'b>],
+) -> Option<&'a mut Descriptor<'b>> {
+    let ix = regs
+        .partition_point(|reg| reg.address <= address)
+        .checked_sub(1)?;
+    match address <= regs[ix].end_address() {
+        true => Some(&mut regs[ix]),
+        false => None,
+    }
+}
+
+impl<'a> Descriptor<'a> {
+    /// Number of consecutive registers spanned by this descriptor
+    pub fn size(&self) -> u16 {
+        match (&self.read, &self.write) {
+            (Some(method), _) => method.size(),
+            (None, Some(method)) => method.size(),
+            (None, None) => 1,
+        }
+    }
+
+    /// Whether the read and write methods span the same number of registers
+    ///
+    /// Descriptors whose spans differ can't be accessed.
+    pub fn spans_match(&self) -> bool {
+        match (&self.read, &self.write) {
+            (Some(read), Some(write)) => read.len() == write.len(),
+            _ => true,
+        }
+    }
+
+    /// Check that the descriptor can be accessed
+    ///
+    /// Values must span between 1 and 65535 registers,
+    /// and the read and write methods must span the same number of registers.
+    pub fn check(&self) -> Result<(), Error> {
+        let read = self.read.as_ref().map(ReadMethod::len);
+        let write = self.write.as_ref().map(WriteMethod::len);
+        if read
+            .into_iter()
+            .chain(write)
+            .any(|len| len == 0 || len > u16::MAX as usize)
+        {
+            return Err(Error::InvalidSize);
+        }
+
+        match self.spans_match() {
+            true => Ok(()),
+            false => Err(Error::SpanMismatch),
+        }
+    }
+
+    /// Address of the last register spanned by this descriptor
+    pub fn end_address(&self) -> u16 {
+        self.address.saturating_add(self.size().saturating_sub(1))
+    }
+
+    pub fn read_allowed(&self) -> bool {
+        match &self.rlock {
+            Some(rlock) => !rlock(),
+            None => true,
+        }
+    }
+
+    /// Whether this descriptor is a block of registers, which can be accessed partially
+    pub fn is_block(&self) -> bool {
+        matches!(self.read, Some(ReadMethod::Block(..)))
+            || matches!(self.write, Some(WriteMethod::Block(..)))
+    }
+
+    /// Offsets into the block of an access of `len` bytes starting at `address`
+    fn block_range(&self, address: u16, len: usize) -> Result<std::ops::Range<usize>, Error> {
+        let start = match address.checked_sub(self.address) {
+            Some(offset) => offset as usize,
+            None => return Err(Error::SpanMismatch),
+        };
+        let end = start + len / 2;
+
+        if !len.is_multiple_of(2) || end > self.size() as usize {
+            return Err(Error::SpanMismatch);
+        }
+
+        Ok(start..end)
+    }
+
+    /// Check that an access of `len` bytes starting at `address` covers the whole value
+    fn check_span(&self, address: u16, len: usize) -> Result<(), Error> {
+        match address == self.address && len == self.size() as usize * 2 {
+            true => Ok(()),
+            false => Err(Error::SpanMismatch),
+        }
+    }
+
+    /// Read the value into `buf` as it should appear on the wire
+    ///
+    /// `buf` must be exactly `2 * size()` bytes long.
+    pub fn read(&self, buf: &mut [u8]) -> Result<(), Error> {
+        self.read_at(self.address, buf)
+    }
+
+    /// Read the registers starting at `address` into `buf` as they should appear on the wire
+    ///
+    /// Only blocks can be read partially,
+    /// other values must be read as a whole with [`Descriptor::read`].
+    pub fn read_at(&self, address: u16, buf: &mut [u8]) -> Result<(), Error> {
+        self.check()?;
+        self.read_at_unchecked(address, buf)
+    }
+
+    /// [`Descriptor::read_at`] for a descriptor that already passed [`Descriptor::check`]
+    pub(crate) fn read_at_unchecked(&self, address: u16, buf: &mut [u8]) -> Result<(), Error> {
+        if !self.read_allowed() {
+            return Err(Error::ReadLocked);
+        }
+
+        match (&self.read, &self.write) {
+            (Some(ReadMethod::Block(block)), _) => {
+                read_block(&block[self.block_range(address, buf.len())?], buf);
+            }
+            (Some(method), _) => {
+                self.check_span(address, buf.len())?;
+                method.read(buf, self.padding, self.scale);
+                self.order.apply(buf);
+            }
+            (None, Some(WriteMethod::Block(block))) => {
+                read_block(&block[self.block_range(address, buf.len())?], buf);
+            }
+            (None, _) => return Err(Error::ReadNotSuppported),
+        }
+
+        Ok(())
+    }
+
+    pub fn write_allowed(&self) -> bool {
+        match &self.wlock {
+            Some(wlock) => !wlock(),
+            None => true,
+        }
+    }
+
+    /// Check that the value in `buf`, as it appears on the wire, can be written
+    ///
+    /// This doesn't check the write lock, see [`Descriptor::write_allowed`].
+    pub fn validate(&self, buf: &[u8]) -> Result<(), Error> {
+        self.validate_at(self.address, buf)
+    }
+
+    /// Check that the registers starting at `address` can be written with `buf`
+    ///
+    /// Only blocks can be written partially, see [`Descriptor::read_at`].
+    pub fn validate_at(&self, address: u16, buf: &[u8]) -> Result<(), Error> {
+        self.check()?;
+        self.validate_at_unchecked(address, buf)
+    }
+
+    /// [`Descriptor::validate_at`] for a descriptor that already passed [`Descriptor::check`]
+    pub(crate) fn validate_at_unchecked(&self, address: u16, buf: &[u8]) -> Result<(), Error> {
+        let bytes = match &self.write {
+            Some(WriteMethod::Block(..)) => {
+                self.block_range(address, buf.len())?;
+                buf.to_vec()
+            }
+            Some(method) => {
+                self.check_span(address, buf.len())?;
+
+                let mut bytes = buf.to_vec();
+                self.order.apply(&mut bytes);
+
+                method.validate(&bytes, self.padding, self.scale)?;
+                bytes
+            }
+            None => return Err(Error::WriteNotSuppported),
+        };
+
+        if let Some(validator) = &self.validator {
+            let words: Vec<u16> = bytes
+                .chunks(2)
+                .map(|w| u16::from_be_bytes([w[0], w[1]]))
+                .collect();
+            validator(&words).map_err(Error::Rejected)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the value from `buf` as it appears on the wire
+    ///
+    /// `buf` must be exactly `2 * size()` bytes long.
+    pub fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.write_at(self.address, buf)
+    }
+
+    /// Write the registers starting at `address` from `buf` as they appear on the wire
+    ///
+    /// Only blocks can be written partially, see [`Descriptor::read_at`].
+    pub fn write_at(&mut self, address: u16, buf: &[u8]) -> Result<(), Error> {
+        self.check()?;
+        self.write_at_unchecked(address, buf)
+    }
+
+    /// [`Descriptor::write_at`] for a descriptor that already passed [`Descriptor::check`]
+    pub(crate) fn write_at_unchecked(&mut self, address: u16, buf: &[u8]) -> Result<(), Error> {
+        if !self.write_allowed() {
+            return Err(Error::WriteLocked);
+        }
+
+        self.validate_at_unchecked(address, buf)?;
+
+        match &mut self.write {
+            Some(WriteMethod::Block(block)) => {
+                let start = (address - self.address) as usize;
+                write_block(&mut block[start..start + buf.len() / 2], buf);
+            }
+            Some(method) => {
+                let mut bytes = buf.to_vec();
+                self.order.apply(&mut bytes);
+
+                method.write(&bytes, self.padding, self.scale)?;
+            }
+            None => return Err(Error::WriteNotSuppported),
+        }
+
+        if let Some(cb) = &mut self.post_write {
+            cb();
+        }
+
+        Ok(())
+    }
+}
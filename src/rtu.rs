@@ -0,0 +1,216 @@
+use std::ops::Deref;
+use std::time::Duration;
+
+use crate::{adu, Parity, SerialConfig};
+
+/// Baud rates above this use fixed inter-character and inter-frame timeouts
+pub const FIXED_TIMING_BAUD: u32 = 19200;
+
+/// Bits of an RTU character with the line settings of the spec, 8E1 or 8N2
+const CHAR_BITS: u64 = 11;
+
+/// Silence timings of an RTU line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// Time to transmit a single character
+    pub char_time: Duration,
+    /// Longest silence allowed between two characters of a frame
+    pub t1_5: Duration,
+    /// Silence marking the end of a frame
+    pub t3_5: Duration,
+}
+
+impl Timing {
+    /// Timings for a baud rate, assuming 11-bit characters
+    ///
+    /// Above [`FIXED_TIMING_BAUD`] the timeouts are fixed to 750µs and 1.75ms as the spec recommends.
+    /// Panics if `baud` is zero.
+    pub fn from_baud(baud: u32) -> Self {
+        Self::with_char_bits(baud, CHAR_BITS)
+    }
+
+    /// Timings for the line settings of a serial config, see [`Timing::from_baud`]
+    ///
+    /// A character is a start bit followed by the data, parity and stop bits,
+    /// so an 8N1 character is only 10 bits.
+    pub fn from_config(config: &SerialConfig) -> Self {
+        let parity = match config.parity {
+            Parity::None => 0,
+            Parity::Even | Parity::Odd => 1,
+        };
+        let bits = 1 + config.data_bits as u64 + parity + config.stop_bits as u64;
+        Self::with_char_bits(config.baud, bits)
+    }
+
+    fn with_char_bits(baud: u32, char_bits: u64) -> Self {
+        let char_time = Duration::from_nanos(char_bits * 1_000_000_000 / baud as u64);
+        match baud > FIXED_TIMING_BAUD {
+            true => Self {
+                char_time,
+                t1_5: Duration::from_micros(750),
+                t3_5: Duration::from_micros(1750),
+            },
+            false => Self {
+                char_time,
+                t1_5: char_time * 3 / 2,
+                t3_5: char_time * 7 / 2,
+            },
+        }
+    }
+}
+
+/// A complete frame cut out of the byte stream
+#[derive(Clone)]
+pub struct Frame {
+    buf: [u8; adu::SIZE_MAX],
+    len: usize,
+    gap_violation: bool,
+    overflow: bool,
+}
+
+impl Frame {
+    /// Silence between two characters of the frame exceeded t1.5
+    ///
+    /// The spec requires such frames to be discarded.
+    pub fn gap_violation(&self) -> bool {
+        self.gap_violation
+    }
+
+    /// More than [`adu::SIZE_MAX`] bytes were received, the excess was dropped
+    pub fn overflow(&self) -> bool {
+        self.overflow
+    }
+
+    /// The frame arrived without any timing or size violation
+    pub fn is_valid(&self) -> bool {
+        !self.gap_violation && !self.overflow
+    }
+}
+
+impl Deref for Frame {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// Assembles RTU frames from a stream of timestamped bytes
+///
+/// Timestamps are the time each byte was received, that is the end of the character,
+/// measured from any fixed point such as the start of the program.
+/// This makes the receiver usable with a virtual clock.
+///
+/// Call [`FrameReceiver::poll`] periodically to pick up the last frame once the line has gone silent.
+pub struct FrameReceiver {
+    timing: Timing,
+    frame: Frame,
+    last: Option<Duration>,
+}
+
+impl FrameReceiver {
+    pub fn new(baud: u32) -> Self {
+        Self::with_timing(Timing::from_baud(baud))
+    }
+
+    pub fn with_timing(timing: Timing) -> Self {
+        Self {
+            timing,
+            frame: Frame {
+                buf: [0; adu::SIZE_MAX],
+                len: 0,
+                gap_violation: false,
+                overflow: false,
+            },
+            last: None,
+        }
+    }
+
+    pub fn timing(&self) -> &Timing {
+        &self.timing
+    }
+
+    /// No part of a frame is pending
+    pub fn is_empty(&self) -> bool {
+        self.frame.len == 0
+    }
+
+    /// Silence on the line between the last byte and `now`
+    fn silence(&self, now: Duration) -> Option<Duration> {
+        Some(now.saturating_sub(self.last?))
+    }
+
+    fn take(&mut self) -> Frame {
+        let frame = self.frame.clone();
+        self.frame.len = 0;
+        self.frame.gap_violation = false;
+        self.frame.overflow = false;
+        frame
+    }
+
+    /// Feed a byte received at `now`
+    ///
+    /// Returns the previous frame if the silence before this byte ended it.
+    pub fn push(&mut self, byte: u8, now: Duration) -> Option<Frame> {
+        // The byte itself took a character time to arrive
+        let silence = self
+            .silence(now)
+            .map(|s| s.saturating_sub(self.timing.char_time));
+
+        let mut done = None;
+        match silence {
+            Some(silence) if silence >= self.timing.t3_5 && self.frame.len > 0 => {
+                done = Some(self.take());
+            }
+            Some(silence) if silence > self.timing.t1_5 && self.frame.len > 0 => {
+                self.frame.gap_violation = true;
+            }
+            _ => {}
+        }
+
+        match self.frame.len < adu::SIZE_MAX {
+            true => {
+                self.frame.buf[self.frame.len] = byte;
+                self.frame.len += 1;
+            }
+            false => self.frame.overflow = true,
+        }
+        self.last = Some(now);
+
+        done
+    }
+
+    /// Feed several bytes read in one go from a UART FIFO, the last of them received at `now`
+    ///
+    /// The bytes are assumed to have arrived back to back,
+    /// so each one is stamped a character time before the next.
+    /// Only the first byte can end the previous frame, the rest are part of the same frame.
+    pub fn push_all(&mut self, bytes: &[u8], now: Duration) -> Option<Frame> {
+        let (first, rest) = bytes.split_first()?;
+        let char_time = self.timing.char_time;
+        let stamp = |i: usize| {
+            let before = (bytes.len() - 1 - i) as u32;
+            now.saturating_sub(char_time * before)
+        };
+
+        let done = self.push(*first, stamp(0));
+        for (i, &byte) in rest.iter().enumerate() {
+            self.push(byte, stamp(i + 1));
+        }
+        done
+    }
+
+    /// Returns the pending frame once the line has been silent for t3.5
+    pub fn poll(&mut self, now: Duration) -> Option<Frame> {
+        match self.silence(now) {
+            Some(silence) if silence >= self.timing.t3_5 && self.frame.len > 0 => Some(self.take()),
+            _ => None,
+        }
+    }
+
+    /// Drop any partially received frame
+    pub fn reset(&mut self) {
+        self.take();
+        self.last = None;
+    }
+}
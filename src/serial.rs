@@ -0,0 +1,207 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::rtu::{Frame, FrameReceiver, Timing};
+use crate::{adu, Counters, Instance, Outcome, Parity, Reason, SerialConfig};
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    match ret {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn speed(baud: u32) -> Option<libc::speed_t> {
+    match baud {
+        1200 => Some(libc::B1200),
+        2400 => Some(libc::B2400),
+        4800 => Some(libc::B4800),
+        9600 => Some(libc::B9600),
+        19200 => Some(libc::B19200),
+        38400 => Some(libc::B38400),
+        57600 => Some(libc::B57600),
+        115200 => Some(libc::B115200),
+        230400 => Some(libc::B230400),
+        _ => None,
+    }
+}
+
+/// Put the port in raw mode with the line settings of `config`
+fn configure(fd: RawFd, config: &SerialConfig) -> io::Result<()> {
+    let speed = speed(config.baud).ok_or_else(|| invalid("unsupported baud rate"))?;
+    if config.data_bits != 8 {
+        return Err(invalid("RTU requires 8 data bits"));
+    }
+    let stop_bits = match config.stop_bits {
+        1 => 0,
+        2 => libc::CSTOPB,
+        _ => return Err(invalid("stop bits must be 1 or 2")),
+    };
+    let parity = match config.parity {
+        Parity::None => 0,
+        Parity::Even => libc::PARENB,
+        Parity::Odd => libc::PARENB | libc::PARODD,
+    };
+
+    // SAFETY: termios is plain data and `fd` is an open file descriptor
+    unsafe {
+        let mut tio: libc::termios = std::mem::zeroed();
+        check(libc::tcgetattr(fd, &mut tio))?;
+        libc::cfmakeraw(&mut tio);
+
+        tio.c_cflag &= !(libc::CSIZE | libc::CSTOPB | libc::PARENB | libc::PARODD);
+        tio.c_cflag |= libc::CLOCAL | libc::CREAD | libc::CS8 | stop_bits | parity;
+        if config.parity != Parity::None {
+            tio.c_iflag |= libc::INPCK;
+        }
+
+        // Reads return whatever is available, the server polls for input itself
+        tio.c_cc[libc::VMIN] = 0;
+        tio.c_cc[libc::VTIME] = 0;
+
+        check(libc::cfsetispeed(&mut tio, speed))?;
+        check(libc::cfsetospeed(&mut tio, speed))?;
+        check(libc::tcsetattr(fd, libc::TCSANOW, &tio))?;
+        check(libc::tcflush(fd, libc::TCIOFLUSH))?;
+    }
+
+    Ok(())
+}
+
+/// RTU slave serving an instance over a serial port
+pub struct Server {
+    port: File,
+    rx: FrameReceiver,
+    /// Clock of the frame receiver
+    epoch: Instant,
+    /// When the last byte was received
+    last_rx: Option<Instant>,
+}
+
+impl Server {
+    /// Open and configure a serial device, such as `/dev/ttyUSB0` or a pty
+    ///
+    /// `config` is usually the one of the served instance.
+    pub fn open(path: impl AsRef<Path>, config: &SerialConfig) -> io::Result<Self> {
+        let port = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+        configure(port.as_raw_fd(), config)?;
+
+        Ok(Self {
+            port,
+            rx: FrameReceiver::with_timing(Timing::from_config(config)),
+            epoch: Instant::now(),
+            last_rx: None,
+        })
+    }
+
+    /// Wait up to `timeout` for input, returns whether the port is readable
+    fn wait(&self, timeout: Duration) -> io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.port.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ms = timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as libc::c_int;
+
+        // SAFETY: `fd` is a single valid pollfd
+        match unsafe { libc::poll(&mut fd, 1, ms) } {
+            -1 => match io::Error::last_os_error() {
+                e if e.kind() == io::ErrorKind::Interrupted => Ok(false),
+                e => Err(e),
+            },
+            0 => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    /// Handle a frame and send the response, if any
+    fn respond(&mut self, inst: &mut Instance, frame: &Frame) -> io::Result<Outcome> {
+        if !frame.is_valid() {
+            Counters::inc(&mut inst.counters.bus_messages);
+        }
+        if frame.overflow() {
+            Counters::inc(&mut inst.counters.overruns);
+            return Ok(Outcome::NoResponse(Reason::Oversized));
+        }
+        if frame.gap_violation() {
+            Counters::inc(&mut inst.counters.bus_comm_errors);
+            return Ok(Outcome::NoResponse(Reason::Gap));
+        }
+
+        let mut res = [0; adu::SIZE_MAX];
+        let outcome = adu::handle_req(inst, frame, &mut res);
+        if let Outcome::Response(size) = outcome {
+            // The line must stay silent for t3.5 after the request before the response starts
+            let timing = self.rx.timing();
+            if let Some(last_rx) = self.last_rx {
+                let ready = last_rx + timing.char_time + timing.t3_5;
+                std::thread::sleep(ready.saturating_duration_since(Instant::now()));
+            }
+
+            self.port.write_all(&res[..size])?;
+            // SAFETY: the port is an open file descriptor
+            check(unsafe { libc::tcdrain(self.port.as_raw_fd()) })?;
+        }
+
+        Ok(outcome)
+    }
+
+    /// Wait up to `timeout` for a request and handle it
+    ///
+    /// Returns `None` if no request arrived in time.
+    /// A request that started arriving is always received in full, even past the timeout.
+    pub fn poll(&mut self, inst: &mut Instance, timeout: Duration) -> io::Result<Option<Outcome>> {
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0; adu::SIZE_MAX];
+
+        loop {
+            if let Some(frame) = self.rx.poll(self.epoch.elapsed()) {
+                return self.respond(inst, &frame).map(Some);
+            }
+
+            // Check back regularly while a frame is pending to notice the end of it
+            let wait = match self.rx.is_empty() {
+                true => deadline.saturating_duration_since(Instant::now()),
+                false => self.rx.timing().t1_5,
+            };
+            if self.rx.is_empty() && wait.is_zero() {
+                return Ok(None);
+            }
+
+            if self.wait(wait)? {
+                let size = match self.port.read(&mut buf) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    res => res?,
+                };
+                if size == 0 {
+                    continue;
+                }
+
+                let now = Instant::now();
+                self.last_rx = Some(now);
+                if let Some(frame) = self.rx.push_all(&buf[..size], now - self.epoch) {
+                    return self.respond(inst, &frame).map(Some);
+                }
+            }
+        }
+    }
+
+    /// Serve requests until an I/O error occurs
+    pub fn run(&mut self, inst: &mut Instance) -> io::Result<()> {
+        loop {
+            self.poll(inst, Duration::from_secs(1))?;
+        }
+    }
+}
@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use mbrs::adu_ascii::FrameReceiver;
+    use mbrs::reg::Descriptor as RegDesc;
+    use mbrs::reg::ReadMethod as RegReadMethod;
+    use mbrs::Reason;
+
+    fn handle(inst: &mut mbrs::Instance, req: &[u8]) -> (mbrs::Outcome, Vec<u8>) {
+        let mut res = [0; mbrs::adu_ascii::SIZE_MAX];
+        let outcome = mbrs::adu_ascii::handle_req(inst, req, &mut res);
+        (outcome, res[..outcome.size()].to_vec())
+    }
+
+    #[test]
+    fn adu_ascii_works() {
+        let regs = &mut mbrs::asc![RegDesc {
+            address: 0x00,
+            read: Some(RegReadMethod::Value(0x1234)),
+            ..Default::default()
+        }];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            serial: Some(mbrs::SerialConfig::new(1)),
+            ..Default::default()
+        };
+
+        let (_, res) = handle(&mut inst, b":010300000001FB\r\n");
+        assert_eq!(res, b":0103021234B4\r\n");
+
+        // Lower case hex is accepted too
+        let (_, res) = handle(&mut inst, b":010300000001fb\r\n");
+        assert_eq!(res, b":0103021234B4\r\n");
+
+        // Exception response
+        let (_, res) = handle(&mut inst, b":010300100001EB\r\n");
+        assert_eq!(res, b":0183027A\r\n");
+    }
+
+    #[test]
+    fn adu_ascii_no_response_works() {
+        let mut inst = mbrs::Instance {
+            serial: Some(mbrs::SerialConfig::new(1)),
+            ..Default::default()
+        };
+
+        let cases: [(&[u8], Reason); 7] = [
+            (b":0103\r\n", Reason::Undersized),
+            (b"010300000001FB\r\n", Reason::Encoding),
+            (b":010300000001FB\n", Reason::Encoding),
+            (b":010300000001F\r\n", Reason::Encoding),
+            (b":01030000000XFB\r\n", Reason::Encoding),
+            (b":010300000001FC\r\n", Reason::Lrc),
+            (b":020300000001FA\r\n", Reason::OtherSlave(2)),
+        ];
+        for (req, reason) in cases {
+            assert_eq!(handle(&mut inst, req).0, mbrs::Outcome::NoResponse(reason));
+        }
+
+        let (outcome, _) = handle(&mut inst, b":000600000001F9\r\n");
+        assert_eq!(outcome, mbrs::Outcome::NoResponse(Reason::Broadcast));
+    }
+
+    #[test]
+    fn adu_ascii_encode_works() {
+        let mut res = [0; mbrs::adu_ascii::SIZE_MAX];
+        let size = mbrs::adu_ascii::encode(0x11, &[0x03, 0x00, 0x6B, 0x00, 0x03], &mut res);
+        assert_eq!(&res[..size], b":1103006B00037E\r\n");
+
+        let mut buf = [0; mbrs::adu::SIZE_MAX];
+        let size = mbrs::adu_ascii::decode(&res[..size], &mut buf).unwrap();
+        assert_eq!(buf[..size], [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x7E]);
+        assert_eq!(mbrs::adu_ascii::lrc(&buf[..size]), 0);
+    }
+
+    #[test]
+    fn adu_ascii_receiver_works() {
+        let mut rx = FrameReceiver::new();
+        let mut now = Duration::ZERO;
+        let mut frames = Vec::new();
+        for &byte in b"xx:01\r\n:0103:0103\r\n".iter() {
+            now += Duration::from_millis(1);
+            if let Some(frame) = rx.push(byte, now) {
+                frames.push(frame.to_vec());
+            }
+        }
+        assert_eq!(frames, [b":01\r\n".to_vec(), b":0103\r\n".to_vec()]);
+    }
+
+    #[test]
+    fn adu_ascii_receiver_timeout_works() {
+        let mut rx = FrameReceiver::with_timeout(Duration::from_millis(500));
+        assert_eq!(rx.timeout(), Duration::from_millis(500));
+
+        let ms = Duration::from_millis;
+        for (i, &byte) in b":0103".iter().enumerate() {
+            assert!(rx.push(byte, ms(i as u64)).is_none());
+        }
+
+        // Frame dropped after the silence, the rest is ignored until the next start
+        assert!(rx.push(b'\r', ms(600)).is_none());
+        assert!(rx.push(b'\n', ms(601)).is_none());
+
+        assert!(rx.push(b':', ms(602)).is_none());
+        assert!(rx.push(b'\r', ms(1000)).is_none());
+        assert_eq!(rx.push(b'\n', ms(1001)).unwrap(), b":\r\n");
+    }
+}
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod test {
     use byteorder::{BigEndian, ByteOrder};
+    use mbrs::{Outcome, Reason};
 
     #[test]
     fn adu_tcp_works() {
@@ -9,7 +10,7 @@ mod test {
 
         let coil1 = false;
 
-        let coils = &mbrs::asc![
+        let coils = &mut mbrs::asc![
             CoilDesc {
                 address: 0x00,
                 read: Some(CoilReadMethod::Value(true)),
@@ -26,7 +27,7 @@ mod test {
                 ..Default::default()
             },
         ];
-        let inst = mbrs::Instance {
+        let mut inst = mbrs::Instance {
             coils: Some(coils),
             ..Default::default()
         };
@@ -41,7 +42,7 @@ mod test {
             0x00, 0x03, // Quantity
         ];
         let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
-        let res_len = mbrs::adu_tcp::handle_req(&inst, &buf, &mut res);
+        let res_len = mbrs::adu_tcp::handle_req(&mut inst, &buf, &mut res).size();
         assert_eq!(res_len, 10);
         assert_eq!(BigEndian::read_u16(&res), 0x0001); // Transaction id
         assert_eq!(BigEndian::read_u16(&res[2..]), 0x0000); // Protocol id
@@ -54,7 +55,7 @@ mod test {
 
     #[test]
     fn adu_tcp_undersized_request_fails() {
-        let inst: mbrs::Instance = Default::default();
+        let mut inst: mbrs::Instance = Default::default();
 
         let buf = [
             0x00, 0x01, // Transation id
@@ -64,13 +65,13 @@ mod test {
                   // Missing function code and data
         ];
         let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
-        let res_len = mbrs::adu_tcp::handle_req(&inst, &buf, &mut res);
-        assert_eq!(res_len, 0);
+        let outcome = mbrs::adu_tcp::handle_req(&mut inst, &buf, &mut res);
+        assert_eq!(outcome, Outcome::NoResponse(Reason::Undersized));
     }
 
     #[test]
     fn adu_tcp_invaid_protocol_id_fails() {
-        let inst: mbrs::Instance = Default::default();
+        let mut inst: mbrs::Instance = Default::default();
 
         let buf = [
             0x00, 0x01, // Transation id
@@ -82,13 +83,13 @@ mod test {
             0x00, 0x03, // Quantity
         ];
         let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
-        let res_len = mbrs::adu_tcp::handle_req(&inst, &buf, &mut res);
-        assert_eq!(res_len, 0);
+        let outcome = mbrs::adu_tcp::handle_req(&mut inst, &buf, &mut res);
+        assert_eq!(outcome, Outcome::NoResponse(Reason::ProtocolId(0x0001)));
     }
 
     #[test]
     fn adu_tcp_transaction_id_echoed_works() {
-        let inst: mbrs::Instance = Default::default();
+        let mut inst: mbrs::Instance = Default::default();
 
         let buf = [
             0x00, 0x01, // Transation id
@@ -100,14 +101,14 @@ mod test {
             0x00, 0x03, // Quantity
         ];
         let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
-        let res_len = mbrs::adu_tcp::handle_req(&inst, &buf, &mut res);
+        let res_len = mbrs::adu_tcp::handle_req(&mut inst, &buf, &mut res).size();
         assert!(res_len > mbrs::adu_tcp::MBAP_SIZE);
         assert_eq!(BigEndian::read_u16(&res), 0x0001); // Transaction id
     }
 
     #[test]
     fn adu_tcp_protocol_id_echoed_works() {
-        let inst: mbrs::Instance = Default::default();
+        let mut inst: mbrs::Instance = Default::default();
 
         let buf = [
             0x00, 0x01, // Transation id
@@ -119,14 +120,14 @@ mod test {
             0x00, 0x03, // Quantity
         ];
         let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
-        let res_len = mbrs::adu_tcp::handle_req(&inst, &buf, &mut res);
+        let res_len = mbrs::adu_tcp::handle_req(&mut inst, &buf, &mut res).size();
         assert!(res_len > mbrs::adu_tcp::MBAP_SIZE);
         assert_eq!(BigEndian::read_u16(&res[2..]), 0x0000); // Protocol id
     }
 
     #[test]
     fn adu_tcp_unit_id_echoed_works() {
-        let inst: mbrs::Instance = Default::default();
+        let mut inst: mbrs::Instance = Default::default();
 
         let buf = [
             0x00, 0x01, // Transation id
@@ -138,24 +139,24 @@ mod test {
             0x00, 0x03, // Quantity
         ];
         let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
-        let res_len = mbrs::adu_tcp::handle_req(&inst, &buf, &mut res);
+        let res_len = mbrs::adu_tcp::handle_req(&mut inst, &buf, &mut res).size();
         assert!(res_len > mbrs::adu_tcp::MBAP_SIZE);
         assert_eq!(res[6], 0x55); // Unit id
     }
 
     #[test]
     fn adu_tcp_zero_size_fails() {
-        let inst: mbrs::Instance = Default::default();
+        let mut inst: mbrs::Instance = Default::default();
 
         let buf: &[u8] = &[];
         let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
-        let res_len = mbrs::adu_tcp::handle_req(&inst, buf, &mut res);
-        assert_eq!(res_len, 0);
+        let outcome = mbrs::adu_tcp::handle_req(&mut inst, buf, &mut res);
+        assert_eq!(outcome, Outcome::NoResponse(Reason::Undersized));
     }
 
     #[test]
     fn adu_tcp_min_size_works() {
-        let inst: mbrs::Instance = Default::default();
+        let mut inst: mbrs::Instance = Default::default();
 
         let buf = [
             0x00, 0x01, // Transation id
@@ -165,7 +166,61 @@ mod test {
             0x01, // Fc: Read coils
         ];
         let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
-        let res_len = mbrs::adu_tcp::handle_req(&inst, &buf, &mut res);
-        assert_eq!(res_len, 0);
+        let outcome = mbrs::adu_tcp::handle_req(&mut inst, &buf, &mut res);
+        assert_eq!(outcome, Outcome::NoResponse(Reason::Undersized));
+    }
+
+    #[test]
+    fn adu_tcp_middleware_sees_peer_works() {
+        use mbrs::pdu::{Middleware, PDUBuf, Transport};
+        use mbrs::StatusCode;
+
+        /// Rejects requests from anything but the loopback address
+        struct Loopback;
+
+        impl Middleware for Loopback {
+            fn before_request(
+                &mut self,
+                transport: &Transport,
+                _req: &mut PDUBuf,
+            ) -> Result<(), StatusCode> {
+                match transport {
+                    Transport::Tcp {
+                        unit_id: 0x01,
+                        peer: Some(peer),
+                    } if peer.ip().is_loopback() => Ok(()),
+                    _ => Err(StatusCode::DeviceFail),
+                }
+            }
+        }
+
+        let mut inst: mbrs::Instance = Default::default();
+        inst.add_middleware(Loopback);
+
+        let buf = [
+            0x00, 0x01, // Transation id
+            0x00, 0x00, // Protocol id
+            0x00, 0x02, // Length
+            0x01, // Unit id
+            0x41, // Fc: User defined
+        ];
+
+        let peer = "127.0.0.1:50200".parse().unwrap();
+        let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
+        let res_len = mbrs::adu_tcp::handle_req_from(&mut inst, &buf, &mut res, peer).size();
+        assert_eq!(res_len, mbrs::adu_tcp::MBAP_SIZE + 2);
+        assert_eq!(res[7..9], [0x41 | 0x80, 0x01]); // Illegal function code
+
+        let peer = "10.0.0.1:50200".parse().unwrap();
+        let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
+        let res_len = mbrs::adu_tcp::handle_req_from(&mut inst, &buf, &mut res, peer).size();
+        assert_eq!(res_len, mbrs::adu_tcp::MBAP_SIZE + 2);
+        assert_eq!(res[7..9], [0x41 | 0x80, 0x04]); // Device failure
+
+        // Unknown peer
+        let mut res = [0; mbrs::adu_tcp::SIZE_MAX];
+        let res_len = mbrs::adu_tcp::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, mbrs::adu_tcp::MBAP_SIZE + 2);
+        assert_eq!(res[7..9], [0x41 | 0x80, 0x04]);
     }
 }
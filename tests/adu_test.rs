@@ -2,8 +2,17 @@
 mod test {
     #[test]
     fn adu_works() {
-        let inst = mbrs::Instance {
-            serial: Some(mbrs::SerialConfig { slave_addr: 1 }),
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+
+        let regs = &mut mbrs::asc![RegDesc {
+            address: 0x00,
+            read: Some(RegReadMethod::Value(0x1234)),
+            ..Default::default()
+        }];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            serial: Some(mbrs::SerialConfig::new(1)),
             ..Default::default()
         };
 
@@ -24,8 +33,182 @@ mod test {
         }
 
         let mut res = [0; mbrs::adu::SIZE_MAX];
-        let res_len = mbrs::adu::handle_req(&inst, &buf, &mut res);
+        let res_len = mbrs::adu::handle_req(&mut inst, &buf, &mut res).size();
 
         assert_eq!(res_len, 7);
+        assert_eq!(res[0], 0x01); // Slave addr
+        assert_eq!(res[1], 0x03); // Function code
+        assert_eq!(res[2], 0x02); // Byte count
+        assert_eq!(res[3..5], [0x12, 0x34]); // Data
+    }
+
+    #[test]
+    fn adu_no_response_reasons_work() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+        use mbrs::{Outcome, Reason};
+
+        fn frame(slave_addr: u8) -> [u8; 8] {
+            let mut buf = [slave_addr, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00];
+            let crc = mbrs::crc::crc16(&buf[..6]).to_le_bytes();
+            buf[6..].copy_from_slice(&crc);
+            buf
+        }
+
+        let regs = &mut mbrs::asc![RegDesc {
+            address: 0x00,
+            read: Some(RegReadMethod::Value(0x1234)),
+            ..Default::default()
+        }];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            serial: Some(mbrs::SerialConfig::new(1)),
+            ..Default::default()
+        };
+
+        let mut res = [0; mbrs::adu::SIZE_MAX];
+        let outcome = mbrs::adu::handle_req(&mut inst, &frame(1), &mut res);
+        assert_eq!(outcome, Outcome::Response(7));
+
+        let outcome = mbrs::adu::handle_req(&mut inst, &frame(2), &mut res);
+        assert_eq!(outcome, Outcome::NoResponse(Reason::OtherSlave(2)));
+
+        let outcome = mbrs::adu::handle_req(&mut inst, &frame(0), &mut res);
+        assert_eq!(outcome, Outcome::NoResponse(Reason::Broadcast));
+
+        let mut buf = frame(1);
+        buf[7] ^= 0xFF;
+        let outcome = mbrs::adu::handle_req(&mut inst, &buf, &mut res);
+        assert_eq!(outcome, Outcome::NoResponse(Reason::Crc));
+
+        let outcome = mbrs::adu::handle_req(&mut inst, &buf[..3], &mut res);
+        assert_eq!(outcome, Outcome::NoResponse(Reason::Undersized));
+
+        inst.serial = None;
+        let outcome = mbrs::adu::handle_req(&mut inst, &frame(1), &mut res);
+        assert_eq!(outcome, Outcome::NoResponse(Reason::NotConfigured));
+    }
+
+    #[test]
+    fn adu_counters_work() {
+        use mbrs::Counters;
+
+        fn frame(pdu: &[u8]) -> Vec<u8> {
+            let mut buf = pdu.to_vec();
+            buf.extend(mbrs::crc::crc16(&buf).to_le_bytes());
+            buf
+        }
+
+        let mut inst = mbrs::Instance {
+            serial: Some(mbrs::SerialConfig::new(1)),
+            handle_fn: Some(Box::new(|_, buf, _| match buf[0] {
+                0x41 => Ok(mbrs::StatusCode::Busy),
+                0x42 => Ok(mbrs::StatusCode::NegaticeAcknowlage),
+                _ => Err(()),
+            })),
+            ..Default::default()
+        };
+
+        let mut res = [0; mbrs::adu::SIZE_MAX];
+        let mut req = |inst: &mut mbrs::Instance, buf: &[u8]| {
+            mbrs::adu::handle_req(inst, buf, &mut res);
+        };
+
+        req(&mut inst, &frame(&[0x01, 0x08, 0x00, 0x00, 0x12, 0x34])); // Return query data
+        req(&mut inst, &frame(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01])); // Illegal function
+        req(&mut inst, &frame(&[0x01, 0x41])); // Busy
+        req(&mut inst, &frame(&[0x01, 0x42])); // Negative acknowledge
+        req(&mut inst, &frame(&[0x02, 0x08, 0x00, 0x00, 0x12, 0x34])); // Other slave
+        req(&mut inst, &frame(&[0x00, 0x08, 0x00, 0x00, 0x12, 0x34])); // Broadcast
+        req(&mut inst, &[0x01, 0x08, 0x00, 0x00, 0x12, 0x34, 0x00, 0x00]); // CRC error
+        req(&mut inst, &[0x01; mbrs::adu::SIZE_MAX + 1]); // Overrun
+
+        assert_eq!(
+            inst.counters,
+            Counters {
+                bus_messages: 8,
+                bus_comm_errors: 1,
+                exceptions: 3,
+                server_messages: 5,
+                no_responses: 1,
+                naks: 1,
+                busy: 1,
+                overruns: 1,
+            }
+        );
+
+        // Listen only mode keeps counting, without responding
+        req(&mut inst, &frame(&[0x01, 0x08, 0x00, 0x04, 0x00, 0x00]));
+        req(&mut inst, &frame(&[0x01, 0x08, 0x00, 0x00, 0x12, 0x34]));
+        assert!(inst.listen_only);
+        assert_eq!(inst.counters.bus_messages, 10);
+        assert_eq!(inst.counters.server_messages, 7);
+        assert_eq!(inst.counters.no_responses, 3);
+
+        // Restart communications clears the counters
+        req(&mut inst, &frame(&[0x01, 0x08, 0x00, 0x01, 0x00, 0x00]));
+        assert!(!inst.listen_only);
+        // Only the restart itself is counted, as it got no response
+        assert_eq!(
+            inst.counters,
+            Counters {
+                no_responses: 1,
+                ..Default::default()
+            }
+        );
+
+        // Read the server message count through FC 08
+        let mut res = [0; mbrs::adu::SIZE_MAX];
+        let buf = frame(&[0x01, 0x08, 0x00, 0x0E, 0x00, 0x00]);
+        let res_len = mbrs::adu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len - 2], [0x01, 0x08, 0x00, 0x0E, 0x00, 0x01]);
+
+        inst.clear_counters();
+        assert_eq!(inst.counters, Counters::default());
+    }
+
+    #[test]
+    fn adu_broadcast_counters_work() {
+        use mbrs::{Counters, Outcome, Reason};
+
+        fn frame(pdu: &[u8]) -> Vec<u8> {
+            let mut buf = pdu.to_vec();
+            buf.extend(mbrs::crc::crc16(&buf).to_le_bytes());
+            buf
+        }
+
+        let mut inst = mbrs::Instance {
+            serial: Some(mbrs::SerialConfig::new(1)),
+            handle_fn: Some(Box::new(|_, buf, _| match buf[0] {
+                0x41 => Ok(mbrs::StatusCode::Busy),
+                0x42 => Ok(mbrs::StatusCode::NegaticeAcknowlage),
+                _ => Err(()),
+            })),
+            ..Default::default()
+        };
+
+        let mut res = [0; mbrs::adu::SIZE_MAX];
+        let mut req =
+            |inst: &mut mbrs::Instance, buf: &[u8]| mbrs::adu::handle_req(inst, buf, &mut res);
+
+        // No exception is returned for a broadcast, so none is counted
+        for pdu in [
+            &[0x00, 0x03, 0x00, 0x00, 0x00, 0x01][..],
+            &[0x00, 0x41],
+            &[0x00, 0x42],
+        ] {
+            let outcome = req(&mut inst, &frame(pdu));
+            assert_eq!(outcome, Outcome::NoResponse(Reason::Broadcast));
+        }
+
+        assert_eq!(
+            inst.counters,
+            Counters {
+                bus_messages: 3,
+                server_messages: 3,
+                no_responses: 3,
+                ..Default::default()
+            }
+        );
     }
 }
@@ -0,0 +1,106 @@
+// Timing benchmarks for large requests, run with `cargo test --release -- --ignored --nocapture`
+//
+// Each one reports the time to handle a request next to the per-address binary search
+// the handlers used to do. The timings depend on the machine, so nothing is asserted on them.
+#[cfg(test)]
+mod test {
+    use std::hint::black_box;
+    use std::time::{Duration, Instant};
+
+    use mbrs::coil::Descriptor as CoilDesc;
+    use mbrs::coil::ReadMethod as CoilReadMethod;
+    use mbrs::reg::Descriptor as RegDesc;
+    use mbrs::reg::ReadMethod as RegReadMethod;
+
+    const ITERATIONS: u32 = 1000;
+
+    fn time(mut f: impl FnMut()) -> Duration {
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            f();
+        }
+        start.elapsed() / ITERATIONS
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_read_2000_coils() {
+        let coils: Vec<CoilDesc> = (0..2000)
+            .map(|address| CoilDesc {
+                address,
+                read: Some(CoilReadMethod::Value(address % 3 == 0)),
+                ..Default::default()
+            })
+            .collect();
+        let mut inst = mbrs::Instance {
+            disc_inputs: Some(&coils),
+            ..Default::default()
+        };
+
+        let buf = [0x02, 0x00, 0x00, 0x07, 0xD0];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let sweep = time(|| {
+            let res_len = mbrs::pdu::handle_req(&mut inst, black_box(&buf), &mut res).size();
+            assert_eq!(res_len, 2 + 250);
+        });
+
+        // A binary search for every address, like the handlers used to do
+        let search = time(|| {
+            let mut bytes = [0u8; 250];
+            for addr in 0..2000u16 {
+                if let Some(c) = mbrs::coil::find(black_box(addr), &coils) {
+                    if c.read().unwrap() {
+                        bytes[addr as usize / 8] |= 1 << (addr % 8);
+                    }
+                }
+            }
+            black_box(bytes);
+        });
+
+        println!("2000 coils: handle_req {sweep:?}, binary search per address {search:?}");
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_read_125_regs() {
+        let regs: Vec<RegDesc> = (0..125)
+            .map(|address| RegDesc {
+                address,
+                read: Some(RegReadMethod::Value(address)),
+                ..Default::default()
+            })
+            .collect();
+        let mut inst = mbrs::Instance {
+            input_regs: Some(&regs),
+            ..Default::default()
+        };
+
+        let buf = [0x04, 0x00, 0x00, 0x00, 0x7D];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let sweep = time(|| {
+            let res_len = mbrs::pdu::handle_req(&mut inst, black_box(&buf), &mut res).size();
+            assert_eq!(res_len, 2 + 250);
+        });
+
+        // A binary search for every address, like the handlers used to do,
+        // once to check the range and once to read it
+        let search = time(|| {
+            for addr in 0..125u16 {
+                if let Some(r) = mbrs::reg::find(black_box(addr), &regs) {
+                    assert!(r.read_allowed() && r.check().is_ok());
+                }
+            }
+
+            let mut bytes = [0u8; 250];
+            for addr in 0..125u16 {
+                if let Some(r) = mbrs::reg::find(black_box(addr), &regs) {
+                    let ix = addr as usize * 2;
+                    r.read(&mut bytes[ix..ix + 2]).unwrap();
+                }
+            }
+            black_box(bytes);
+        });
+
+        println!("125 registers: handle_req {sweep:?}, binary search per address {search:?}");
+    }
+}
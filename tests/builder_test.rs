@@ -0,0 +1,233 @@
+#[cfg(test)]
+mod test {
+    use mbrs::builder::Table;
+    use mbrs::coil::Descriptor as CoilDesc;
+    use mbrs::coil::ReadMethod as CoilReadMethod;
+    use mbrs::reg::Descriptor as RegDesc;
+    use mbrs::reg::ReadMethod as RegReadMethod;
+    use mbrs::reg::Source;
+    use mbrs::{BuildError, Instance};
+
+    fn coil(address: u16, value: bool) -> CoilDesc<'static> {
+        CoilDesc {
+            address,
+            read: Some(CoilReadMethod::Value(value)),
+            ..Default::default()
+        }
+    }
+
+    fn reg(address: u16, read: RegReadMethod<'static>) -> RegDesc<'static> {
+        RegDesc {
+            address,
+            read: Some(read),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn builder_sorts_points_works() {
+        let coils = &mut [coil(0x02, true), coil(0x00, true), coil(0x01, false)];
+        let regs = &mut [
+            reg(0x10, RegReadMethod::Value(0x1234)),
+            reg(0x00, RegReadMethod::U32(Source::Value(0x0001_0002))),
+        ];
+
+        let mut inst = Instance::builder()
+            .coils(coils)
+            .holding_regs(regs)
+            .serial(mbrs::SerialConfig::new(1))
+            .build()
+            .unwrap();
+
+        let buf = [0x01, 0x00, 0x00, 0x00, 0x03];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 3);
+        assert_eq!(res[2], 0b101);
+
+        let buf = [0x03, 0x00, 0x00, 0x00, 0x02];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 6);
+        assert_eq!(res[2..6], [0x00, 0x01, 0x00, 0x02]);
+
+        let buf = [0x03, 0x00, 0x10, 0x00, 0x01];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 4);
+        assert_eq!(res[2..4], [0x12, 0x34]);
+    }
+
+    #[test]
+    fn builder_duplicate_fails() {
+        let coils = &mut [coil(0x01, true), coil(0x00, true), coil(0x01, false)];
+
+        let err = Instance::builder()
+            .disc_inputs(coils)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            BuildError::Duplicate {
+                table: Table::DiscInputs,
+                address: 0x01
+            }
+        );
+        assert_eq!(err.to_string(), "discrete inputs: duplicate address 0x0001");
+    }
+
+    #[test]
+    fn builder_overlap_fails() {
+        let regs = &mut [
+            reg(0x03, RegReadMethod::Value(0)),
+            reg(0x00, RegReadMethod::U64(Source::Value(0))),
+        ];
+
+        let err = Instance::builder().input_regs(regs).build().err().unwrap();
+        assert_eq!(
+            err,
+            BuildError::Overlap {
+                table: Table::InputRegs,
+                address: 0x03,
+                span: (0x00, 0x03),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "input registers: 0x0003 overlaps 0x0000..=0x0003"
+        );
+    }
+
+    #[test]
+    fn builder_out_of_range_fails() {
+        let regs = &mut [reg(0xFFFF, RegReadMethod::U32(Source::Value(0)))];
+
+        let err = Instance::builder()
+            .holding_regs(regs)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            BuildError::OutOfRange {
+                table: Table::HoldingRegs,
+                address: 0xFFFF
+            }
+        );
+    }
+
+    #[test]
+    fn builder_empty_block_fails() {
+        use mbrs::coil::WriteMethod as CoilWriteMethod;
+
+        let regs = &mut [
+            reg(0x00, RegReadMethod::Value(0)),
+            reg(0x01, RegReadMethod::Block(&[])),
+        ];
+
+        let err = Instance::builder()
+            .holding_regs(regs)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            BuildError::InvalidSize {
+                table: Table::HoldingRegs,
+                address: 0x01
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "holding registers: 0x0001 is empty or spans over 65535 addresses"
+        );
+
+        let coils = &mut [CoilDesc {
+            address: 0x00,
+            write: Some(CoilWriteMethod::Bits {
+                size: 0,
+                block: &mut [],
+            }),
+            ..Default::default()
+        }];
+
+        let err = Instance::builder().coils(coils).build().err().unwrap();
+        assert_eq!(
+            err,
+            BuildError::InvalidSize {
+                table: Table::Coils,
+                address: 0x00
+            }
+        );
+    }
+
+    #[test]
+    fn builder_span_mismatch_fails() {
+        use mbrs::coil::WriteMethod as CoilWriteMethod;
+        use mbrs::reg::{Sink, WriteMethod as RegWriteMethod};
+
+        let regs = &mut [RegDesc {
+            address: 0x10,
+            read: Some(RegReadMethod::Value(0)),
+            write: Some(RegWriteMethod::U32(Sink::Fn(Box::new(|_| ())))),
+            ..Default::default()
+        }];
+
+        let err = Instance::builder()
+            .holding_regs(regs)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            BuildError::SpanMismatch {
+                table: Table::HoldingRegs,
+                address: 0x10
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "holding registers: 0x0010 reads and writes a different number of addresses"
+        );
+
+        let mut slice = [false; 2];
+        let coils = &mut [CoilDesc {
+            address: 0x00,
+            read: Some(CoilReadMethod::Bits {
+                size: 8,
+                block: &[0x00],
+            }),
+            write: Some(CoilWriteMethod::Slice(&mut slice)),
+            ..Default::default()
+        }];
+
+        let err = Instance::builder().coils(coils).build().err().unwrap();
+        assert_eq!(
+            err,
+            BuildError::SpanMismatch {
+                table: Table::Coils,
+                address: 0x00
+            }
+        );
+    }
+
+    #[test]
+    fn builder_slave_addr_fails() {
+        for slave_addr in [0, 248, 255] {
+            let err = Instance::builder()
+                .serial(mbrs::SerialConfig::new(slave_addr))
+                .build()
+                .err()
+                .unwrap();
+            assert_eq!(err, BuildError::SlaveAddr(slave_addr));
+        }
+
+        for slave_addr in [mbrs::adu::SLAVE_ADDR_MIN, mbrs::adu::SLAVE_ADDR_MAX] {
+            let inst = Instance::builder()
+                .serial(mbrs::SerialConfig::new(slave_addr))
+                .build();
+            assert!(inst.is_ok());
+        }
+    }
+}
@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod test {
+    use mbrs::map::ModbusMap;
+    use mbrs_derive::ModbusMap;
+
+    #[derive(Default, ModbusMap)]
+    struct Pump {
+        #[coil(0x11)]
+        enabled: bool,
+        #[coil(0x10)]
+        running: bool,
+        #[discrete(0x00)]
+        fault: bool,
+        #[holding(100)]
+        setpoint: u16,
+        #[holding(101, order = "CDAB")]
+        total: u32,
+        #[input(200, scale = 0.1)]
+        temperature: f32,
+        #[input(201, scale = 0.5, offset = -10, signed = true)]
+        pressure: f32,
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    fn req(map: &mut mbrs::map::Map, buf: &[u8]) -> Vec<u8> {
+        let mut inst = map.instance();
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, buf, &mut res).size();
+        res[..res_len].to_vec()
+    }
+
+    #[test]
+    fn derive_sorts_tables_works() {
+        let mut pump = Pump::default();
+        let map = pump.modbus_map();
+
+        let coils: Vec<u16> = map.coils.iter().map(|c| c.address).collect();
+        assert_eq!(coils, [0x10, 0x11]);
+        assert_eq!(map.disc_inputs.len(), 1);
+        assert_eq!(map.holding_regs.len(), 2);
+        assert_eq!(map.input_regs.len(), 2);
+    }
+
+    #[test]
+    fn derive_read_works() {
+        let mut pump = Pump {
+            running: true,
+            fault: true,
+            setpoint: 0x1234,
+            total: 0x0001_0002,
+            temperature: 21.5,
+            pressure: -12.0,
+            ..Default::default()
+        };
+        let mut map = pump.modbus_map();
+
+        assert_eq!(
+            req(&mut map, &[0x01, 0x00, 0x10, 0x00, 0x02]),
+            [0x01, 0x01, 0b01]
+        );
+        assert_eq!(
+            req(&mut map, &[0x02, 0x00, 0x00, 0x00, 0x01]),
+            [0x02, 0x01, 0b1]
+        );
+        assert_eq!(
+            req(&mut map, &[0x03, 0x00, 100, 0x00, 0x03]),
+            [0x03, 0x06, 0x12, 0x34, 0x00, 0x02, 0x00, 0x01]
+        );
+        // 21.5 / 0.1 = 215 and (-12 + 10) / 0.5 = -4
+        assert_eq!(
+            req(&mut map, &[0x04, 0x00, 200, 0x00, 0x02]),
+            [0x04, 0x04, 0x00, 0xD7, 0xFF, 0xFC]
+        );
+    }
+
+    #[test]
+    fn derive_write_works() {
+        let mut pump = Pump::default();
+        let mut map = pump.modbus_map();
+
+        req(&mut map, &[0x05, 0x00, 0x11, 0xFF, 0x00]);
+        req(&mut map, &[0x06, 0x00, 100, 0xAB, 0xCD]);
+        req(
+            &mut map,
+            &[0x10, 0x00, 101, 0x00, 0x02, 0x04, 0x00, 0x04, 0x00, 0x03],
+        );
+
+        // Input registers and discrete inputs are read-only
+        let res = req(&mut map, &[0x05, 0x00, 0x00, 0xFF, 0x00]);
+        assert_eq!(res[0], 0x85);
+        drop(map);
+
+        assert!(pump.enabled);
+        assert!(!pump.running);
+        assert_eq!(pump.setpoint, 0xABCD);
+        assert_eq!(pump.total, 0x0003_0004);
+    }
+}
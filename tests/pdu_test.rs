@@ -2,11 +2,11 @@
 mod test {
     #[test]
     fn pdu_too_little_data() {
-        let inst: mbrs::Instance = Default::default();
+        let mut inst: mbrs::Instance = Default::default();
 
         let buf = [0x04];
         let mut res = [0; mbrs::pdu::SIZE_MAX];
-        let res_len = mbrs::pdu::handle_req(&inst, &buf, &mut res);
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
         assert_eq!(res_len, 2);
         assert_eq!(res[0], 0x04 | 0x80); // Error response
         assert_eq!(res[1], 0x03); // Illegal data value
@@ -20,7 +20,7 @@ mod test {
         let coil1 = false;
         let coil2 = true;
 
-        let coils = &mbrs::asc![
+        let coils = &mut mbrs::asc![
             CoilDesc {
                 address: 0x00,
                 read: Some(CoilReadMethod::Value(true)),
@@ -37,7 +37,7 @@ mod test {
                 ..Default::default()
             },
         ];
-        let inst = mbrs::Instance {
+        let mut inst = mbrs::Instance {
             coils: Some(coils),
             ..Default::default()
         };
@@ -48,37 +48,1486 @@ mod test {
             0x00, 0x03, // Quantity
         ];
         let mut res = [0; mbrs::pdu::SIZE_MAX];
-        let res_len = mbrs::pdu::handle_req(&inst, &buf, &mut res);
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
         assert_eq!(res_len, 3);
         assert_eq!(res[0], 0x01);
         assert_eq!(res[1], 0x01);
         assert_eq!(res[2], 0b0101);
     }
 
+    #[test]
+    fn pdu_read_holding_regs_works() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+
+        let reg1 = 0x5678;
+
+        let regs = &mut mbrs::asc![
+            RegDesc {
+                address: 0x10,
+                read: Some(RegReadMethod::Value(0x1234)),
+                ..Default::default()
+            },
+            RegDesc {
+                address: 0x11,
+                read: Some(RegReadMethod::Ref(&reg1)),
+                ..Default::default()
+            },
+            // 0x12 is missing and is read as zero
+            RegDesc {
+                address: 0x13,
+                read: Some(RegReadMethod::Fn(Box::new(|| 0xBEEF))),
+                ..Default::default()
+            },
+        ];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            ..Default::default()
+        };
+
+        let buf = [
+            0x03, // Fc: Read holding registers
+            0x00, 0x10, // Start address
+            0x00, 0x04, // Quantity
+        ];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 10);
+        assert_eq!(res[0], 0x03);
+        assert_eq!(res[1], 0x08); // Byte count
+        assert_eq!(res[2..10], [0x12, 0x34, 0x56, 0x78, 0x00, 0x00, 0xBE, 0xEF]);
+    }
+
+    #[test]
+    fn pdu_read_input_regs_missing_start_fails() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+
+        let regs = &mbrs::asc![RegDesc {
+            address: 0x01,
+            read: Some(RegReadMethod::Value(0x1234)),
+            ..Default::default()
+        }];
+        let mut inst = mbrs::Instance {
+            input_regs: Some(regs),
+            ..Default::default()
+        };
+
+        let buf = [
+            0x04, // Fc: Read input registers
+            0x00, 0x00, // Start address
+            0x00, 0x02, // Quantity
+        ];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 2);
+        assert_eq!(res[0], 0x04 | 0x80); // Error response
+        assert_eq!(res[1], 0x02); // Illegal data address
+    }
+
+    #[test]
+    fn pdu_write_single_reg_works() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::WriteMethod as RegWriteMethod;
+
+        let mut reg1 = 0;
+        let mut reg2 = 0;
+        let mut n_post_write = 0;
+
+        {
+            let regs = &mut mbrs::asc![
+                RegDesc {
+                    address: 0x01,
+                    write: Some(RegWriteMethod::Ref(&mut reg1)),
+                    post_write: Some(Box::new(|| n_post_write += 1)),
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x02,
+                    write: Some(RegWriteMethod::Fn(Box::new(|v| reg2 = v))),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            for (addr, value) in [(0x01, 0x1234), (0x02, 0xABCD)] {
+                let buf = [
+                    0x06, // Fc: Write single register
+                    0x00,
+                    addr, // Address
+                    (value >> 8) as u8,
+                    value as u8, // Value
+                ];
+                let mut res = [0; mbrs::pdu::SIZE_MAX];
+                let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+                assert_eq!(res_len, 5);
+                assert_eq!(res[..5], buf); // Echo
+            }
+        }
+
+        assert_eq!(reg1, 0x1234);
+        assert_eq!(reg2, 0xABCD);
+        assert_eq!(n_post_write, 1);
+    }
+
+    #[test]
+    fn pdu_write_single_reg_locked_fails() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::WriteMethod as RegWriteMethod;
+
+        let mut reg1 = 0;
+
+        {
+            let regs = &mut mbrs::asc![RegDesc {
+                address: 0x01,
+                write: Some(RegWriteMethod::Ref(&mut reg1)),
+                wlock: Some(Box::new(|| true)),
+                ..Default::default()
+            }];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            for addr in [0x01, 0x02] {
+                let buf = [
+                    0x06, // Fc: Write single register
+                    0x00, addr, // Address
+                    0x12, 0x34, // Value
+                ];
+                let mut res = [0; mbrs::pdu::SIZE_MAX];
+                let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+                assert_eq!(res_len, 2);
+                assert_eq!(res[0], 0x06 | 0x80); // Error response
+                assert_eq!(res[1], 0x02); // Illegal data address
+            }
+        }
+
+        assert_eq!(reg1, 0);
+    }
+
+    #[test]
+    fn pdu_write_multiple_regs_works() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::WriteMethod as RegWriteMethod;
+
+        let mut reg1 = 0;
+        let mut reg2 = 0;
+
+        {
+            let regs = &mut mbrs::asc![
+                RegDesc {
+                    address: 0x01,
+                    write: Some(RegWriteMethod::Ref(&mut reg1)),
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x02,
+                    write: Some(RegWriteMethod::Fn(Box::new(|v| reg2 = v))),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            let buf = [
+                0x10, // Fc: Write multiple registers
+                0x00, 0x01, // Start address
+                0x00, 0x02, // Quantity
+                0x04, // Byte count
+                0x12, 0x34, 0xAB, 0xCD, // Data
+            ];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+            assert_eq!(res[..5], buf[..5]); // Echo address and quantity
+        }
+
+        assert_eq!(reg1, 0x1234);
+        assert_eq!(reg2, 0xABCD);
+    }
+
+    #[test]
+    fn pdu_write_multiple_regs_all_or_nothing() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::WriteMethod as RegWriteMethod;
+
+        let mut reg1 = 0;
+        let mut reg2 = 0;
+
+        {
+            let regs = &mut mbrs::asc![
+                RegDesc {
+                    address: 0x01,
+                    write: Some(RegWriteMethod::Ref(&mut reg1)),
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x02,
+                    write: Some(RegWriteMethod::Ref(&mut reg2)),
+                    wlock: Some(Box::new(|| true)),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            // Second register is locked, third doesn't exist
+            for quantity in [2, 3] {
+                let buf = [
+                    0x10, // Fc: Write multiple registers
+                    0x00,
+                    0x01, // Start address
+                    0x00,
+                    quantity,     // Quantity
+                    quantity * 2, // Byte count
+                    0x12,
+                    0x34,
+                    0xAB,
+                    0xCD,
+                    0x00,
+                    0x01, // Data
+                ];
+                let buf = &buf[..6 + quantity as usize * 2];
+                let mut res = [0; mbrs::pdu::SIZE_MAX];
+                let res_len = mbrs::pdu::handle_req(&mut inst, buf, &mut res).size();
+                assert_eq!(res_len, 2);
+                assert_eq!(res[0], 0x10 | 0x80); // Error response
+                assert_eq!(res[1], 0x02); // Illegal data address
+            }
+        }
+
+        assert_eq!(reg1, 0);
+        assert_eq!(reg2, 0);
+    }
+
+    #[test]
+    fn pdu_write_multiple_regs_invalid_frame_fails() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::WriteMethod as RegWriteMethod;
+
+        let mut reg1 = 0;
+
+        {
+            let regs = &mut mbrs::asc![RegDesc {
+                address: 0x01,
+                write: Some(RegWriteMethod::Ref(&mut reg1)),
+                ..Default::default()
+            }];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            let bufs: [&[u8]; 3] = [
+                // Quantity above 123
+                &[0x10, 0x00, 0x01, 0x00, 0x7C, 0xF8, 0x00, 0x00],
+                // Byte count doesn't match quantity
+                &[0x10, 0x00, 0x01, 0x00, 0x01, 0x03, 0x12, 0x34, 0x00],
+                // Frame shorter than byte count
+                &[0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x12, 0x34],
+            ];
+            for buf in bufs {
+                let mut res = [0; mbrs::pdu::SIZE_MAX];
+                let res_len = mbrs::pdu::handle_req(&mut inst, buf, &mut res).size();
+                assert_eq!(res_len, 2);
+                assert_eq!(res[0], 0x10 | 0x80); // Error response
+                assert_eq!(res[1], 0x03); // Illegal data value
+            }
+        }
+
+        assert_eq!(reg1, 0);
+    }
+
+    #[test]
+    fn pdu_mask_write_reg_works() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+        use mbrs::reg::WriteMethod as RegWriteMethod;
+        use std::cell::Cell;
+
+        let reg1 = Cell::new(0x0012);
+
+        let regs = &mut mbrs::asc![
+            RegDesc {
+                address: 0x04,
+                read: Some(RegReadMethod::Fn(Box::new(|| reg1.get()))),
+                write: Some(RegWriteMethod::Fn(Box::new(|v| reg1.set(v)))),
+                ..Default::default()
+            },
+            RegDesc {
+                address: 0x05,
+                read: Some(RegReadMethod::Value(0x0012)),
+                ..Default::default()
+            },
+        ];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            ..Default::default()
+        };
+
+        let buf = [
+            0x16, // Fc: Mask write register
+            0x00, 0x04, // Address
+            0x00, 0xF2, // And mask
+            0x00, 0x25, // Or mask
+        ];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 7);
+        assert_eq!(res[..7], buf); // Echo
+        assert_eq!(reg1.get(), 0x0017);
+
+        // Read only register
+        let buf = [0x16, 0x00, 0x05, 0x00, 0xF2, 0x00, 0x25];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 2);
+        assert_eq!(res[0], 0x16 | 0x80); // Error response
+        assert_eq!(res[1], 0x02); // Illegal data address
+    }
+
+    #[test]
+    fn pdu_read_write_regs_works() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+        use mbrs::reg::WriteMethod as RegWriteMethod;
+        use std::cell::Cell;
+
+        let reg1 = Cell::new(0x0000);
+
+        let regs = &mut mbrs::asc![
+            RegDesc {
+                address: 0x01,
+                read: Some(RegReadMethod::Fn(Box::new(|| reg1.get()))),
+                write: Some(RegWriteMethod::Fn(Box::new(|v| reg1.set(v)))),
+                ..Default::default()
+            },
+            RegDesc {
+                address: 0x02,
+                read: Some(RegReadMethod::Value(0x5678)),
+                ..Default::default()
+            },
+        ];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            ..Default::default()
+        };
+
+        let buf = [
+            0x17, // Fc: Read/write multiple registers
+            0x00, 0x01, // Read start address
+            0x00, 0x02, // Read quantity
+            0x00, 0x01, // Write start address
+            0x00, 0x01, // Write quantity
+            0x02, // Write byte count
+            0x12, 0x34, // Write data
+        ];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 6);
+        assert_eq!(res[0], 0x17);
+        assert_eq!(res[1], 0x04); // Byte count
+        assert_eq!(res[2..6], [0x12, 0x34, 0x56, 0x78]); // Written value is read back
+        assert_eq!(reg1.get(), 0x1234);
+    }
+
+    #[test]
+    fn pdu_read_locked_coil_fails() {
+        use mbrs::coil::Descriptor as CoilDesc;
+        use mbrs::coil::ReadMethod as CoilReadMethod;
+        use std::cell::Cell;
+
+        let locked = Cell::new(true);
+
+        let coils = &mut mbrs::asc![CoilDesc {
+            address: 0x00,
+            read: Some(CoilReadMethod::Value(true)),
+            rlock: Some(Box::new(|| locked.get())),
+            ..Default::default()
+        }];
+        let mut inst = mbrs::Instance {
+            coils: Some(coils),
+            ..Default::default()
+        };
+
+        let buf = [0x01, 0x00, 0x00, 0x00, 0x01];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len], [0x01 | 0x80, 0x02]);
+
+        locked.set(false);
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len], [0x01, 0x01, 0b1]);
+    }
+
+    #[test]
+    fn pdu_read_locked_reg_fails() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+        use std::cell::Cell;
+
+        let locked = Cell::new(true);
+
+        let regs = &mut mbrs::asc![RegDesc {
+            address: 0x00,
+            read: Some(RegReadMethod::Value(0x1234)),
+            rlock: Some(Box::new(|| locked.get())),
+            ..Default::default()
+        }];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            ..Default::default()
+        };
+
+        let buf = [0x03, 0x00, 0x00, 0x00, 0x01];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len], [0x03 | 0x80, 0x02]);
+
+        locked.set(false);
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len], [0x03, 0x02, 0x12, 0x34]);
+    }
+
+    #[test]
+    fn pdu_read_write_regs_validates_up_front() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+        use mbrs::reg::WriteMethod as RegWriteMethod;
+        use std::cell::Cell;
+
+        let reg1 = Cell::new(0x0000);
+
+        let regs = &mut mbrs::asc![
+            RegDesc {
+                address: 0x01,
+                read: Some(RegReadMethod::Fn(Box::new(|| reg1.get()))),
+                write: Some(RegWriteMethod::Fn(Box::new(|v| reg1.set(v)))),
+                ..Default::default()
+            },
+            RegDesc {
+                address: 0x02,
+                read: Some(RegReadMethod::Value(0x5678)),
+                rlock: Some(Box::new(|| true)),
+                ..Default::default()
+            },
+        ];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            ..Default::default()
+        };
+
+        let cases: [(&[u8], u8); 4] = [
+            // Read range starts at missing register
+            (
+                &[
+                    0x17, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x02, 0x12, 0x34,
+                ],
+                0x02,
+            ),
+            // Read range covers a read locked register
+            (
+                &[
+                    0x17, 0x00, 0x01, 0x00, 0x02, 0x00, 0x01, 0x00, 0x01, 0x02, 0x12, 0x34,
+                ],
+                0x02,
+            ),
+            // Read quantity above 125
+            (
+                &[
+                    0x17, 0x00, 0x01, 0x00, 0x7E, 0x00, 0x01, 0x00, 0x01, 0x02, 0x12, 0x34,
+                ],
+                0x03,
+            ),
+            // Write quantity above 121
+            (
+                &[
+                    0x17, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x7A, 0xF4, 0x12, 0x34,
+                ],
+                0x03,
+            ),
+        ];
+        for (buf, status) in cases {
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[0], 0x17 | 0x80); // Error response
+            assert_eq!(res[1], status);
+        }
+
+        assert_eq!(reg1.get(), 0x0000);
+    }
+
     #[test]
     fn pdu_write_single_coil_fn_works() {
         use mbrs::coil::Descriptor as CoilDesc;
         use mbrs::coil::WriteMethod as CoilWriteMethod;
 
         let mut coil1 = false;
+        let mut coil2 = true;
+
+        {
+            let coils = &mut mbrs::asc![
+                CoilDesc {
+                    address: 0x00,
+                    write: Some(CoilWriteMethod::Fn(Box::new(|v| coil1 = v))),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x01,
+                    write: Some(CoilWriteMethod::Ref(&mut coil2)),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                coils: Some(coils),
+                ..Default::default()
+            };
+
+            for (addr, value) in [(0x00, 0xFF), (0x01, 0x00)] {
+                let buf = [
+                    0x05, // Fc: Write single coil
+                    0x00, addr, // Address
+                    value, 0x00, // Value
+                ];
+                let mut res = [0; mbrs::pdu::SIZE_MAX];
+                let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+                assert_eq!(res_len, 5);
+                assert_eq!(res[..5], buf); // Echo
+            }
+
+            // Invalid coil value
+            let buf = [0x05, 0x00, 0x00, 0x12, 0x34];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[0], 0x05 | 0x80); // Error response
+            assert_eq!(res[1], 0x03); // Illegal data value
+        }
+
+        assert!(coil1);
+        assert!(!coil2);
+    }
+
+    #[test]
+    fn pdu_write_multiple_coils_works() {
+        use mbrs::coil::Descriptor as CoilDesc;
+        use mbrs::coil::WriteMethod as CoilWriteMethod;
+
+        let mut coils_val = [false; 10];
+
+        {
+            let [c0, c1, c2, c3, c4, c5, c6, c7, c8, c9] = &mut coils_val;
+            let coils = &mut mbrs::asc![
+                CoilDesc {
+                    address: 0x13,
+                    write: Some(CoilWriteMethod::Ref(c0)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x14,
+                    write: Some(CoilWriteMethod::Ref(c1)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x15,
+                    write: Some(CoilWriteMethod::Ref(c2)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x16,
+                    write: Some(CoilWriteMethod::Ref(c3)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x17,
+                    write: Some(CoilWriteMethod::Ref(c4)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x18,
+                    write: Some(CoilWriteMethod::Ref(c5)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x19,
+                    write: Some(CoilWriteMethod::Ref(c6)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x1A,
+                    write: Some(CoilWriteMethod::Ref(c7)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x1B,
+                    write: Some(CoilWriteMethod::Ref(c8)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x1C,
+                    write: Some(CoilWriteMethod::Ref(c9)),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                coils: Some(coils),
+                ..Default::default()
+            };
+
+            let buf = [
+                0x0F, // Fc: Write multiple coils
+                0x00, 0x13, // Start address
+                0x00, 0x0A, // Quantity
+                0x02, // Byte count
+                0xCD, 0x01, // Data
+            ];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+            assert_eq!(res[..5], buf[..5]); // Echo address and quantity
+        }
+
+        assert_eq!(
+            coils_val,
+            [true, false, true, true, false, false, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn pdu_write_coils_hooks_work() {
+        use mbrs::coil::Descriptor as CoilDesc;
+        use mbrs::coil::WriteMethod as CoilWriteMethod;
+        use std::cell::Cell;
+
+        let n_post_write = Cell::new(0);
+        let n_commit = Cell::new(0);
+
+        let coils = &mut mbrs::asc![
+            CoilDesc {
+                address: 0x00,
+                write: Some(CoilWriteMethod::Fn(Box::new(|_| ()))),
+                post_write: Some(Box::new(|| n_post_write.set(n_post_write.get() + 1))),
+                ..Default::default()
+            },
+            CoilDesc {
+                address: 0x01,
+                write: Some(CoilWriteMethod::Fn(Box::new(|_| ()))),
+                post_write: Some(Box::new(|| n_post_write.set(n_post_write.get() + 1))),
+                ..Default::default()
+            },
+            CoilDesc {
+                address: 0x02,
+                write: Some(CoilWriteMethod::Fn(Box::new(|_| ()))),
+                wlock: Some(Box::new(|| true)),
+                post_write: Some(Box::new(|| n_post_write.set(n_post_write.get() + 1))),
+                ..Default::default()
+            },
+        ];
+        let mut inst = mbrs::Instance {
+            coils: Some(coils),
+            commit_coil_write: Some(Box::new(|| n_commit.set(n_commit.get() + 1))),
+            ..Default::default()
+        };
+
+        let buf = [
+            0x05, // Fc: Write single coil
+            0x00, 0x00, // Address
+            0xFF, 0x00, // Value
+        ];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(n_post_write.get(), 1);
+        assert_eq!(n_commit.get(), 1);
+
+        let buf = [
+            0x0F, // Fc: Write multiple coils
+            0x00, 0x00, // Start address
+            0x00, 0x02, // Quantity
+            0x01, // Byte count
+            0x03, // Data
+        ];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(n_post_write.get(), 3);
+        assert_eq!(n_commit.get(), 2);
+
+        // Third coil is locked, so nothing is written
+        let buf = [0x0F, 0x00, 0x00, 0x00, 0x03, 0x01, 0x07];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 2);
+        assert_eq!(res[1], 0x02); // Illegal data address
+        assert_eq!(n_post_write.get(), 3);
+        assert_eq!(n_commit.get(), 2);
+    }
+
+    #[test]
+    fn pdu_write_coils_hook_order_works() {
+        use mbrs::coil::Descriptor as CoilDesc;
+        use mbrs::coil::ReadMethod as CoilReadMethod;
+        use mbrs::coil::WriteMethod as CoilWriteMethod;
+        use std::cell::{Cell, RefCell};
+
+        let coil1 = Cell::new(false);
+        let coil2 = Cell::new(false);
+        let events = RefCell::new(Vec::new());
 
-        let coils = &mbrs::asc![CoilDesc {
+        let coils = &mut mbrs::asc![
+            CoilDesc {
+                address: 0x00,
+                read: Some(CoilReadMethod::Fn(Box::new(|| coil1.get()))),
+                write: Some(CoilWriteMethod::Fn(Box::new(|v| coil1.set(v)))),
+                post_write: Some(Box::new(|| events.borrow_mut().push("post_write 0"))),
+                ..Default::default()
+            },
+            CoilDesc {
+                address: 0x01,
+                read: Some(CoilReadMethod::Fn(Box::new(|| coil2.get()))),
+                write: Some(CoilWriteMethod::Fn(Box::new(|v| coil2.set(v)))),
+                post_write: Some(Box::new(|| events.borrow_mut().push("post_write 1"))),
+                ..Default::default()
+            },
+        ];
+        let mut inst = mbrs::Instance {
+            coils: Some(coils),
+            stage_coil_write: Some(Box::new(|_| {
+                // Nothing is written yet
+                assert!(!coil1.get() && !coil2.get());
+                events.borrow_mut().push("stage");
+                Ok(())
+            })),
+            commit_coil_write: Some(Box::new(|| {
+                // The new values are visible to push them to hardware
+                assert!(coil1.get() && coil2.get());
+                events.borrow_mut().push("commit");
+            })),
+            ..Default::default()
+        };
+
+        let buf = [0x0F, 0x00, 0x00, 0x00, 0x02, 0x01, 0x03];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 5);
+        assert_eq!(
+            *events.borrow(),
+            ["stage", "post_write 0", "post_write 1", "commit"]
+        );
+    }
+
+    #[test]
+    fn pdu_user_handle_fn_works() {
+        let mut inst = mbrs::Instance {
+            serial: Some(mbrs::SerialConfig::new(0x11)),
+            handle_fn: Some(Box::new(|inst, buf, res| match buf[0] {
+                // Report slave id
+                0x11 => {
+                    res.p[1] = 2; // Byte count
+                    res.p[2] = inst.serial.as_ref().unwrap().slave_addr;
+                    res.p[3] = 0xFF; // Run indicator status
+                    res.size = 4;
+                    Ok(mbrs::StatusCode::Ok)
+                }
+                // User defined function code, handled with an exception
+                0x41 => Ok(mbrs::StatusCode::DeviceFail),
+                _ => Err(()),
+            })),
+            ..Default::default()
+        };
+
+        let buf = [0x11]; // Fc: Report slave id
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 4);
+        assert_eq!(res[..4], [0x11, 0x02, 0x11, 0xFF]);
+
+        let buf = [0x41]; // User defined function code
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 2);
+        assert_eq!(res[..2], [0x41 | 0x80, 0x04]); // Device failure
+
+        let buf = [0x42]; // Not handled
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 2);
+        assert_eq!(res[..2], [0x42 | 0x80, 0x01]); // Illegal function code
+    }
+
+    #[test]
+    fn pdu_function_handler_works() {
+        use mbrs::coil::Descriptor as CoilDesc;
+        use mbrs::coil::ReadMethod as CoilReadMethod;
+        use mbrs::pdu::{FunctionHandler, PDUBuf};
+        use mbrs::{Instance, StatusCode};
+
+        /// Vendor specific function code returning a fixed payload
+        struct Vendor {
+            n_calls: usize,
+        }
+
+        impl FunctionHandler for Vendor {
+            fn handle(
+                &mut self,
+                _inst: &mut Instance,
+                _buf: &[u8],
+                res: &mut PDUBuf,
+            ) -> Result<StatusCode, ()> {
+                self.n_calls += 1;
+                res.p[1] = self.n_calls as u8;
+                res.size = 2;
+                Ok(StatusCode::Ok)
+            }
+        }
+
+        let coils = &mut mbrs::asc![CoilDesc {
             address: 0x00,
-            write: Some(CoilWriteMethod::Fn(Box::new(|v| coil1 = v))),
+            read: Some(CoilReadMethod::Value(true)),
             ..Default::default()
         }];
-        let _inst = mbrs::Instance {
+        let mut inst = mbrs::Instance {
             coils: Some(coils),
             ..Default::default()
         };
+        inst.register_handler(0x41, Vendor { n_calls: 0 });
+        // Override read coils for a single address, otherwise use the built-in handler
+        inst.register_handler(
+            0x01,
+            |_: &mut Instance, buf: &[u8], _: &mut PDUBuf| match buf[1..3] {
+                [0xFF, 0xFF] => Ok(StatusCode::IllegalDataAddr),
+                _ => Err(()),
+            },
+        );
 
-        // TODO
-        /*let buf = [
-            0x01, // Fc: Read coils
-            0x00, 0x00, // Start address
-            0x00, 0x03, // Quantity
+        for n_calls in 1..=2 {
+            let buf = [0x41];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[..2], [0x41, n_calls]);
+        }
+
+        let buf = [0x01, 0xFF, 0xFF, 0x00, 0x01];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 2);
+        assert_eq!(res[..2], [0x01 | 0x80, 0x02]);
+
+        let buf = [0x01, 0x00, 0x00, 0x00, 0x01];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 3);
+        assert_eq!(res[..3], [0x01, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn pdu_middleware_works() {
+        use std::cell::RefCell;
+
+        use mbrs::pdu::{Middleware, PDUBuf, Transport};
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+        use mbrs::StatusCode;
+
+        /// Only allows reads from serial slave address 1
+        struct ReadOnly;
+
+        impl Middleware for ReadOnly {
+            fn before_request(
+                &mut self,
+                transport: &Transport,
+                req: &mut PDUBuf,
+            ) -> Result<(), StatusCode> {
+                match (transport, req.p[0]) {
+                    (_, 0x03) => Ok(()),
+                    (Transport::Serial { slave_addr: 1 }, _) => Ok(()),
+                    _ => Err(StatusCode::IllegalFc),
+                }
+            }
+        }
+
+        /// Maps holding register 0x10 onto 0x00
+        struct Remap;
+
+        impl Middleware for Remap {
+            fn before_request(
+                &mut self,
+                _transport: &Transport,
+                req: &mut PDUBuf,
+            ) -> Result<(), StatusCode> {
+                if req.p[..3] == [0x03, 0x00, 0x10] {
+                    req.p[2] = 0x00;
+                }
+                Ok(())
+            }
+        }
+
+        /// Records the transport, low address byte and response of every request
+        struct Log<'a>(&'a RefCell<Vec<(Transport, u8, Vec<u8>)>>);
+
+        impl Middleware for Log<'_> {
+            fn after_response(&mut self, transport: &Transport, req: &[u8], res: &[u8]) {
+                self.0.borrow_mut().push((*transport, req[2], res.to_vec()));
+            }
+        }
+
+        let log = RefCell::new(Vec::new());
+
+        let regs = &mut mbrs::asc![RegDesc {
+            address: 0x00,
+            read: Some(RegReadMethod::Value(0x1234)),
+            ..Default::default()
+        }];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            ..Default::default()
+        };
+        inst.add_middleware(ReadOnly);
+        inst.add_middleware(Remap);
+        inst.add_middleware(Log(&log));
+
+        // Rewritten request
+        let buf = [0x03, 0x00, 0x10, 0x00, 0x01];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 4);
+        assert_eq!(res[..4], [0x03, 0x02, 0x12, 0x34]);
+
+        // Rejected request
+        let buf = [0x06, 0x00, 0x00, 0x00, 0x01];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 2);
+        assert_eq!(res[..2], [0x06 | 0x80, 0x01]);
+
+        // Passed on from the serial slave, but the register isn't writable
+        let transport = Transport::Serial { slave_addr: 1 };
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req_with(&mut inst, &transport, &buf, &mut res).size();
+        assert_eq!(res_len, 2);
+        assert_eq!(res[..2], [0x06 | 0x80, 0x02]);
+
+        let log = log.borrow();
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[0].0, Transport::Local);
+        assert_eq!(log[0].1, 0x00); // Rewritten address
+        assert_eq!(log[0].2, [0x03, 0x02, 0x12, 0x34]);
+        assert_eq!(log[1].2, [0x06 | 0x80, 0x01]); // Rejected requests are observed too
+        assert_eq!(log[2].0, transport);
+        assert_eq!(log[2].2, [0x06 | 0x80, 0x02]);
+    }
+
+    #[test]
+    fn pdu_write_regs_validator_works() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::WriteMethod as RegWriteMethod;
+        use mbrs::StatusCode;
+
+        let mut setpoint = 50;
+        let mut mode = 0;
+
+        {
+            let regs = &mut mbrs::asc![
+                RegDesc {
+                    address: 0x00,
+                    write: Some(RegWriteMethod::Ref(&mut setpoint)),
+                    validator: Some(Box::new(|v| match v[0] {
+                        10..=90 => Ok(()),
+                        _ => Err(StatusCode::IllegalDataValue),
+                    })),
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x01,
+                    write: Some(RegWriteMethod::Ref(&mut mode)),
+                    validator: Some(Box::new(|v| match v[0] {
+                        0..=3 => Ok(()),
+                        _ => Err(StatusCode::DeviceFail),
+                    })),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            let buf = [0x06, 0x00, 0x00, 0x00, 0x5B]; // Setpoint 91
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[..2], [0x06 | 0x80, 0x03]);
+
+            // Setpoint is valid, but the mode isn't, so nothing is written
+            let buf = [0x10, 0x00, 0x00, 0x00, 0x02, 0x04, 0x00, 0x14, 0x00, 0x04];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[..2], [0x10 | 0x80, 0x04]);
+
+            let buf = [0x10, 0x00, 0x00, 0x00, 0x02, 0x04, 0x00, 0x14, 0x00, 0x03];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+        }
+
+        assert_eq!(setpoint, 20);
+        assert_eq!(mode, 3);
+    }
+
+    #[test]
+    fn pdu_write_coils_validator_works() {
+        use mbrs::coil::Descriptor as CoilDesc;
+        use mbrs::coil::WriteMethod as CoilWriteMethod;
+        use mbrs::StatusCode;
+
+        let mut coil1 = false;
+        let mut coil2 = false;
+
+        {
+            let coils = &mut mbrs::asc![
+                CoilDesc {
+                    address: 0x00,
+                    write: Some(CoilWriteMethod::Ref(&mut coil1)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x01,
+                    write: Some(CoilWriteMethod::Ref(&mut coil2)),
+                    // Can only be switched off remotely
+                    validator: Some(Box::new(|v| match v {
+                        false => Ok(()),
+                        true => Err(StatusCode::IllegalDataValue),
+                    })),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                coils: Some(coils),
+                ..Default::default()
+            };
+
+            let buf = [0x05, 0x00, 0x01, 0xFF, 0x00];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[..2], [0x05 | 0x80, 0x03]);
+
+            let buf = [0x0F, 0x00, 0x00, 0x00, 0x02, 0x01, 0x03];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[..2], [0x0F | 0x80, 0x03]);
+
+            let buf = [0x0F, 0x00, 0x00, 0x00, 0x02, 0x01, 0x01];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+        }
+
+        assert!(coil1);
+        assert!(!coil2);
+    }
+
+    #[test]
+    fn pdu_write_coils_change_set_works() {
+        use mbrs::coil::Change as CoilChange;
+        use mbrs::coil::Descriptor as CoilDesc;
+        use mbrs::coil::ReadMethod as CoilReadMethod;
+        use mbrs::coil::WriteMethod as CoilWriteMethod;
+        use std::cell::{Cell, RefCell};
+
+        let coil1 = Cell::new(false);
+        let changes = RefCell::new(Vec::new());
+
+        let coils = &mut mbrs::asc![
+            CoilDesc {
+                address: 0x00,
+                read: Some(CoilReadMethod::Fn(Box::new(|| coil1.get()))),
+                write: Some(CoilWriteMethod::Fn(Box::new(|v| coil1.set(v)))),
+                ..Default::default()
+            },
+            CoilDesc {
+                address: 0x01,
+                write: Some(CoilWriteMethod::Fn(Box::new(|_| ()))),
+                ..Default::default()
+            },
+        ];
+        let mut inst = mbrs::Instance {
+            coils: Some(coils),
+            stage_coil_write: Some(Box::new(|c: &[CoilChange]| {
+                changes.borrow_mut().push(c.to_vec());
+                // Veto switching both coils on at once
+                match c.iter().all(|c| c.new) {
+                    true => Err(()),
+                    false => Ok(()),
+                }
+            })),
+            ..Default::default()
+        };
+
+        let buf = [0x0F, 0x00, 0x00, 0x00, 0x02, 0x01, 0x03];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 2);
+        assert_eq!(res[..2], [0x0F | 0x80, 0x04]); // Device failure
+        assert!(!coil1.get());
+
+        let buf = [0x0F, 0x00, 0x00, 0x00, 0x02, 0x01, 0x01];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 5);
+        assert!(coil1.get());
+
+        let changes = changes.borrow();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[1],
+            [
+                CoilChange {
+                    address: 0x00,
+                    old: Some(false),
+                    new: true,
+                },
+                CoilChange {
+                    address: 0x01,
+                    old: None, // Write only
+                    new: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pdu_write_regs_change_set_works() {
+        use mbrs::reg::Change as RegChange;
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+        use mbrs::reg::WriteMethod as RegWriteMethod;
+        use mbrs::reg::{Sink, Source};
+        use std::cell::{Cell, RefCell};
+
+        let reg1 = Cell::new(0x0001);
+        let reg2 = Cell::new(0x0002_0003);
+        let changes = RefCell::new(Vec::new());
+        let veto = Cell::new(true);
+
+        let regs = &mut mbrs::asc![
+            RegDesc {
+                address: 0x00,
+                read: Some(RegReadMethod::Fn(Box::new(|| reg1.get()))),
+                write: Some(RegWriteMethod::Fn(Box::new(|v| reg1.set(v)))),
+                ..Default::default()
+            },
+            RegDesc {
+                address: 0x01,
+                read: Some(RegReadMethod::U32(Source::Fn(Box::new(|| reg2.get())))),
+                write: Some(RegWriteMethod::U32(Sink::Fn(Box::new(|v| reg2.set(v))))),
+                ..Default::default()
+            },
         ];
-        let mut res = [0; PDU_SIZE_MAX];
-        let res_len = handle_req(&inst, &buf, &mut res);*/
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            commit_reg_write: Some(Box::new(|c: &[RegChange]| {
+                changes.borrow_mut().push(c.to_vec());
+                match veto.get() {
+                    true => Err(()),
+                    false => Ok(()),
+                }
+            })),
+            ..Default::default()
+        };
+
+        let buf = [
+            0x10, 0x00, 0x00, 0x00, 0x03, 0x06, 0x00, 0x0A, 0x00, 0x0B, 0x00, 0x0C,
+        ];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 2);
+        assert_eq!(res[..2], [0x10 | 0x80, 0x04]); // Device failure
+        assert_eq!(reg1.get(), 0x0001);
+        assert_eq!(reg2.get(), 0x0002_0003);
+
+        veto.set(false);
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 5);
+        assert_eq!(reg1.get(), 0x000A);
+        assert_eq!(reg2.get(), 0x000B_000C);
+
+        // Mask write is staged as well
+        let buf = [0x16, 0x00, 0x00, 0x00, 0xF0, 0x00, 0x05];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 7);
+        assert_eq!(reg1.get(), 0x0005);
+
+        let changes = changes.borrow();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(
+            changes[1],
+            [
+                RegChange {
+                    address: 0x00,
+                    old: Some(0x0001),
+                    new: 0x000A,
+                },
+                RegChange {
+                    address: 0x01,
+                    old: Some(0x0002),
+                    new: 0x000B,
+                },
+                RegChange {
+                    address: 0x02,
+                    old: Some(0x0003),
+                    new: 0x000C,
+                },
+            ]
+        );
+        assert_eq!(
+            changes[2],
+            [RegChange {
+                address: 0x00,
+                old: Some(0x000A),
+                new: 0x0005,
+            }]
+        );
+    }
+
+    #[test]
+    fn pdu_coil_blocks_work() {
+        use mbrs::coil::Descriptor as CoilDesc;
+        use mbrs::coil::ReadMethod as CoilReadMethod;
+        use mbrs::coil::WriteMethod as CoilWriteMethod;
+
+        let mut slice = [true, false, true];
+        let mut bits = [0b1000_0001, 0x00];
+
+        {
+            let coils = &mut mbrs::asc![
+                CoilDesc {
+                    address: 0x00,
+                    read: Some(CoilReadMethod::Value(true)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x01,
+                    write: Some(CoilWriteMethod::Slice(&mut slice)),
+                    ..Default::default()
+                },
+                CoilDesc {
+                    address: 0x08,
+                    write: Some(CoilWriteMethod::Bits {
+                        size: 12,
+                        block: &mut bits,
+                    }),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                coils: Some(coils),
+                ..Default::default()
+            };
+
+            let buf = [0x01, 0x00, 0x00, 0x00, 0x18]; // Read 24 coils
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+            assert_eq!(res[1..5], [0x03, 0b0000_1011, 0b1000_0001, 0x00]);
+
+            // Start in the middle of a block
+            let buf = [0x01, 0x00, 0x02, 0x00, 0x02];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 3);
+            assert_eq!(res[2], 0b10);
+
+            // Write across the end of the slice and into the bitset, skipping the gap fails
+            let buf = [0x0F, 0x00, 0x03, 0x00, 0x07, 0x01, 0x7F];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[..2], [0x0F | 0x80, 0x02]);
+
+            let buf = [0x0F, 0x00, 0x09, 0x00, 0x0A, 0x02, 0xFF, 0x02];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+
+            let buf = [0x05, 0x00, 0x02, 0xFF, 0x00];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+
+            // The bitset only spans 12 coils, the rest of its last byte doesn't exist
+            let buf = [0x05, 0x00, 0x14, 0xFF, 0x00];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[..2], [0x05 | 0x80, 0x02]);
+        }
+
+        assert_eq!(slice, [true, true, true]);
+        assert_eq!(bits, [0b1111_1111, 0b0000_0101]);
+    }
+
+    #[test]
+    fn pdu_invalid_blocks_fail() {
+        use mbrs::coil::Descriptor as CoilDesc;
+        use mbrs::coil::ReadMethod as CoilReadMethod;
+        use mbrs::coil::WriteMethod as CoilWriteMethod;
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+
+        let bits = [0x00];
+        let mut slice = [false; 2];
+        let large = vec![false; 0x10000];
+
+        let coils = &mut mbrs::asc![
+            CoilDesc {
+                address: 0x00,
+                read: Some(CoilReadMethod::Bits {
+                    size: 8,
+                    block: &bits,
+                }),
+                write: Some(CoilWriteMethod::Slice(&mut slice)),
+                ..Default::default()
+            },
+            CoilDesc {
+                address: 0x10,
+                read: Some(CoilReadMethod::Bits {
+                    size: 0,
+                    block: &[],
+                }),
+                ..Default::default()
+            },
+            CoilDesc {
+                address: 0x20,
+                read: Some(CoilReadMethod::Bits {
+                    size: 9,
+                    block: &bits,
+                }),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(coils[0].check(), Err(mbrs::coil::Error::SpanMismatch));
+        assert_eq!(coils[1].check(), Err(mbrs::coil::Error::InvalidSize));
+        assert_eq!(coils[2].check(), Err(mbrs::coil::Error::InvalidSize));
+
+        let disc_inputs = &mbrs::asc![CoilDesc {
+            address: 0x00,
+            read: Some(CoilReadMethod::Slice(&large)),
+            ..Default::default()
+        }];
+        assert_eq!(disc_inputs[0].size(), 0xFFFF);
+        assert_eq!(disc_inputs[0].check(), Err(mbrs::coil::Error::InvalidSize));
+
+        let regs = &mut mbrs::asc![RegDesc {
+            address: 0x00,
+            read: Some(RegReadMethod::Block(&[])),
+            ..Default::default()
+        }];
+
+        let mut inst = mbrs::Instance {
+            coils: Some(coils),
+            disc_inputs: Some(disc_inputs),
+            holding_regs: Some(regs),
+            ..Default::default()
+        };
+
+        let reqs: [&[u8]; 6] = [
+            &[0x05, 0x00, 0x05, 0xFF, 0x00],
+            &[0x01, 0x00, 0x00, 0x00, 0x08],
+            &[0x01, 0x00, 0x10, 0x00, 0x01],
+            &[0x01, 0x00, 0x20, 0x00, 0x01],
+            &[0x02, 0x00, 0x00, 0x00, 0x01],
+            &[0x03, 0x00, 0x00, 0x00, 0x01],
+        ];
+        for buf in reqs {
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[..2], [buf[0] | 0x80, 0x04]);
+        }
+    }
+
+    #[test]
+    fn pdu_diagnostics_works() {
+        let mut inst = mbrs::Instance::default();
+
+        // Return query data
+        let buf = [0x08, 0x00, 0x00, 0xA5, 0x37, 0x12];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len], buf);
+
+        // Counters, bus message count through overrun count
+        for sub_fn in 0x0B..=0x12 {
+            let buf = [0x08, 0x00, sub_fn, 0x00, 0x00];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res[..res_len], [0x08, 0x00, sub_fn, 0x00, 0x00]);
+        }
+
+        // Clear counters
+        let buf = [0x08, 0x00, 0x0A, 0x00, 0x00];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len], buf);
+
+        // Invalid data
+        let buf = [0x08, 0x00, 0x0B, 0x00, 0x01];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len], [0x88, 0x03]);
+
+        // Unsupported sub-function
+        let buf = [0x08, 0x00, 0x02, 0x00, 0x00];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len], [0x88, 0x01]);
+    }
+
+    #[test]
+    fn pdu_diagnostics_listen_only_works() {
+        let mut inst = mbrs::Instance::default();
+
+        // Force listen only mode, never answered
+        let buf = [0x08, 0x00, 0x04, 0x00, 0x00];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let outcome = mbrs::pdu::handle_req(&mut inst, &buf, &mut res);
+        assert_eq!(outcome, mbrs::Outcome::NoResponse(mbrs::Reason::ListenOnly));
+
+        let buf = [0x08, 0x00, 0x00, 0x12, 0x34];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let outcome = mbrs::pdu::handle_req(&mut inst, &buf, &mut res);
+        assert_eq!(outcome, mbrs::Outcome::NoResponse(mbrs::Reason::ListenOnly));
+
+        // Restart communications leaves listen only mode, without a response
+        let buf = [0x08, 0x00, 0x01, 0xFF, 0x00];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let outcome = mbrs::pdu::handle_req(&mut inst, &buf, &mut res);
+        assert_eq!(outcome, mbrs::Outcome::NoResponse(mbrs::Reason::ListenOnly));
+
+        let buf = [0x08, 0x00, 0x00, 0x12, 0x34];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len], buf);
+
+        // Restart communications outside of listen only mode is answered
+        let buf = [0x08, 0x00, 0x01, 0x00, 0x00];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res[..res_len], buf);
     }
 }
@@ -0,0 +1,560 @@
+#[cfg(test)]
+mod test {
+    use mbrs::reg::Descriptor as RegDesc;
+    use mbrs::reg::{
+        BytesSink, BytesSource, Order, Padding, ReadMethod, Scale, Sink, Source, StrSink,
+        StrSource, WriteMethod,
+    };
+
+    #[test]
+    fn reg_read_u32_orders_work() {
+        let cases = [
+            (Order::Abcd, [0xAA, 0xBB, 0xCC, 0xDD]),
+            (Order::Cdab, [0xCC, 0xDD, 0xAA, 0xBB]),
+            (Order::Badc, [0xBB, 0xAA, 0xDD, 0xCC]),
+            (Order::Dcba, [0xDD, 0xCC, 0xBB, 0xAA]),
+        ];
+
+        for (order, expected) in cases {
+            let reg = RegDesc {
+                address: 0x00,
+                read: Some(ReadMethod::U32(Source::Value(0xAABBCCDD))),
+                order,
+                ..Default::default()
+            };
+            assert_eq!(reg.size(), 2);
+
+            let mut buf = [0; 4];
+            reg.read(&mut buf).unwrap();
+            assert_eq!(buf, expected);
+        }
+    }
+
+    #[test]
+    fn reg_read_u64_orders_work() {
+        let cases = [
+            (
+                Order::Abcd,
+                [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88],
+            ),
+            (
+                Order::Cdab,
+                [0x77, 0x88, 0x55, 0x66, 0x33, 0x44, 0x11, 0x22],
+            ),
+            (
+                Order::Badc,
+                [0x22, 0x11, 0x44, 0x33, 0x66, 0x55, 0x88, 0x77],
+            ),
+            (
+                Order::Dcba,
+                [0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11],
+            ),
+        ];
+
+        let value = 0x1122334455667788;
+        for (order, expected) in cases {
+            let reg = RegDesc {
+                address: 0x00,
+                read: Some(ReadMethod::U64(Source::Ref(&value))),
+                order,
+                ..Default::default()
+            };
+            assert_eq!(reg.size(), 4);
+
+            let mut buf = [0; 8];
+            reg.read(&mut buf).unwrap();
+            assert_eq!(buf, expected);
+        }
+    }
+
+    #[test]
+    fn reg_write_f32_cdab_works() {
+        let mut value = 0.0f32;
+
+        {
+            let mut reg = RegDesc {
+                address: 0x00,
+                write: Some(WriteMethod::F32(Sink::Ref(&mut value))),
+                order: Order::Cdab,
+                ..Default::default()
+            };
+
+            let be = 1.5f32.to_be_bytes();
+            reg.write(&[be[2], be[3], be[0], be[1]]).unwrap();
+        }
+
+        assert_eq!(value, 1.5);
+    }
+
+    #[test]
+    fn reg_partial_access_fails() {
+        let reg = RegDesc {
+            address: 0x00,
+            read: Some(ReadMethod::I32(Source::Fn(Box::new(|| -1)))),
+            ..Default::default()
+        };
+
+        let mut buf = [0; 2];
+        assert_eq!(reg.read(&mut buf), Err(mbrs::reg::Error::SpanMismatch));
+    }
+
+    #[test]
+    fn reg_span_mismatch_fails() {
+        let mut low = 0u16;
+        let mut text = String::new();
+
+        {
+            let regs = &mut mbrs::asc![
+                RegDesc {
+                    address: 0x00,
+                    read: Some(ReadMethod::Value(0x0001)),
+                    write: Some(WriteMethod::U32(Sink::Fn(Box::new(|_| ())))),
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x10,
+                    read: Some(ReadMethod::U32(Source::Value(0x0002_0003))),
+                    write: Some(WriteMethod::Ref(&mut low)),
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x20,
+                    read: Some(ReadMethod::Str {
+                        size: 2,
+                        source: StrSource::Value("ab"),
+                    }),
+                    write: Some(WriteMethod::Str {
+                        size: 3,
+                        sink: StrSink::Ref(&mut text),
+                    }),
+                    ..Default::default()
+                },
+            ];
+            assert!(regs.iter().all(|r| !r.spans_match()));
+
+            let mut buf = [0; 4];
+            assert_eq!(regs[1].read(&mut buf), Err(mbrs::reg::Error::SpanMismatch));
+
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            let reqs: [&[u8]; 4] = [
+                &[0x06, 0x00, 0x00, 0x12, 0x34],
+                &[0x06, 0x00, 0x10, 0x12, 0x34],
+                &[0x10, 0x00, 0x10, 0x00, 0x02, 0x04, 0x12, 0x34, 0x56, 0x78],
+                &[0x10, 0x00, 0x20, 0x00, 0x02, 0x04, b'c', b'd', b'e', b'f'],
+            ];
+            for buf in reqs {
+                let mut res = [0; mbrs::pdu::SIZE_MAX];
+                let res_len = mbrs::pdu::handle_req(&mut inst, buf, &mut res).size();
+                assert_eq!(res_len, 2);
+                assert_eq!(res[..2], [buf[0] | 0x80, 0x04]);
+            }
+        }
+
+        assert_eq!(low, 0);
+        assert!(text.is_empty());
+    }
+
+    #[test]
+    fn reg_read_typed_regs_works() {
+        let regs = &mut mbrs::asc![
+            RegDesc {
+                address: 0x10,
+                read: Some(ReadMethod::Value(0x0001)),
+                ..Default::default()
+            },
+            RegDesc {
+                address: 0x11,
+                read: Some(ReadMethod::F32(Source::Value(1.5))),
+                order: Order::Cdab,
+                ..Default::default()
+            },
+            RegDesc {
+                address: 0x13,
+                read: Some(ReadMethod::I64(Source::Value(-2))),
+                ..Default::default()
+            },
+        ];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            ..Default::default()
+        };
+
+        let buf = [
+            0x03, // Fc: Read holding registers
+            0x00, 0x10, // Start address
+            0x00, 0x07, // Quantity
+        ];
+        let mut res = [0; mbrs::pdu::SIZE_MAX];
+        let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+        assert_eq!(res_len, 16);
+        assert_eq!(res[1], 14); // Byte count
+        assert_eq!(res[2..4], [0x00, 0x01]);
+        assert_eq!(res[4..8], [0x00, 0x00, 0x3F, 0xC0]); // 1.5f32 CDAB
+        assert_eq!(res[8..16], [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE]);
+
+        // Start address and end address in the middle of a value
+        for (addr, quantity) in [(0x12, 0x01), (0x10, 0x02), (0x13, 0x03)] {
+            let buf = [0x03, 0x00, addr, 0x00, quantity];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[0], 0x03 | 0x80); // Error response
+            assert_eq!(res[1], 0x02); // Illegal data address
+        }
+    }
+
+    #[test]
+    fn reg_write_typed_regs_works() {
+        let mut reg1 = 0u32;
+        let mut reg2 = 0i32;
+
+        {
+            let regs = &mut mbrs::asc![
+                RegDesc {
+                    address: 0x10,
+                    write: Some(WriteMethod::U32(Sink::Ref(&mut reg1))),
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x12,
+                    write: Some(WriteMethod::I32(Sink::Fn(Box::new(|v| reg2 = v)))),
+                    order: Order::Dcba,
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            // Partial writes are rejected
+            for buf in [
+                &[0x06, 0x00, 0x10, 0x12, 0x34][..],
+                &[0x10, 0x00, 0x11, 0x00, 0x02, 0x04, 0x00, 0x00, 0x00, 0x00][..],
+                &[
+                    0x10, 0x00, 0x10, 0x00, 0x03, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                ][..],
+            ] {
+                let mut res = [0; mbrs::pdu::SIZE_MAX];
+                let res_len = mbrs::pdu::handle_req(&mut inst, buf, &mut res).size();
+                assert_eq!(res_len, 2);
+                assert_eq!(res[0], buf[0] | 0x80); // Error response
+                assert_eq!(res[1], 0x02); // Illegal data address
+            }
+
+            let buf = [
+                0x10, // Fc: Write multiple registers
+                0x00, 0x10, // Start address
+                0x00, 0x04, // Quantity
+                0x08, // Byte count
+                0x12, 0x34, 0x56, 0x78, // u32 ABCD
+                0xFE, 0xFF, 0xFF, 0xFF, // i32 DCBA
+            ];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+        }
+
+        assert_eq!(reg1, 0x12345678);
+        assert_eq!(reg2, -2);
+    }
+
+    #[test]
+    fn reg_read_str_works() {
+        let cases = [
+            (Padding::Nul, Order::Abcd, *b"ACME\0\0"),
+            (Padding::Space, Order::Abcd, *b"ACME  "),
+            (Padding::Space, Order::Badc, *b"CAEM  "),
+        ];
+
+        for (padding, order, expected) in cases {
+            let reg = RegDesc {
+                address: 0x00,
+                read: Some(ReadMethod::Str {
+                    size: 3,
+                    source: StrSource::Value("ACME"),
+                }),
+                order,
+                padding,
+                ..Default::default()
+            };
+            assert_eq!(reg.size(), 3);
+
+            let mut buf = [0; 6];
+            reg.read(&mut buf).unwrap();
+            assert_eq!(buf, expected);
+        }
+
+        // Longer strings are truncated
+        let serial = String::from("SN-0123456789");
+        let reg = RegDesc {
+            address: 0x00,
+            read: Some(ReadMethod::Str {
+                size: 2,
+                source: StrSource::Ref(&serial),
+            }),
+            ..Default::default()
+        };
+        let mut buf = [0; 4];
+        reg.read(&mut buf).unwrap();
+        assert_eq!(&buf, b"SN-0");
+    }
+
+    #[test]
+    fn reg_read_bytes_works() {
+        let reg = RegDesc {
+            address: 0x00,
+            read: Some(ReadMethod::Bytes {
+                size: 2,
+                source: BytesSource::Fn(Box::new(|| vec![0x01, 0x02, 0x03])),
+            }),
+            ..Default::default()
+        };
+
+        let mut buf = [0xFF; 4];
+        reg.read(&mut buf).unwrap();
+        assert_eq!(buf, [0x01, 0x02, 0x03, 0x00]);
+    }
+
+    #[test]
+    fn reg_write_str_works() {
+        let mut name = String::from("old name");
+        let mut raw = [0; 4];
+
+        {
+            let regs = &mut mbrs::asc![
+                RegDesc {
+                    address: 0x10,
+                    write: Some(WriteMethod::Str {
+                        size: 4,
+                        sink: StrSink::Ref(&mut name),
+                    }),
+                    order: Order::Badc,
+                    padding: Padding::Space,
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x14,
+                    write: Some(WriteMethod::Bytes {
+                        size: 2,
+                        sink: BytesSink::Ref(&mut raw),
+                    }),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            let buf = [
+                0x10, // Fc: Write multiple registers
+                0x00, 0x10, // Start address
+                0x00, 0x06, // Quantity
+                0x0C, // Byte count
+                b'U', b'P', b'P', b'M', b'1', b' ', b' ', b' ', // "PUMP 1" swapped
+                0xDE, 0xAD, 0xBE, 0xEF, // Raw bytes
+            ];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+        }
+
+        assert_eq!(name, "PUMP 1");
+        assert_eq!(raw, [0xDE, 0xAD, 0xBE, 0xEF]);
+    }
+
+    #[test]
+    fn reg_write_invalid_str_fails() {
+        let mut name = String::from("name");
+        let mut reg1 = 0;
+
+        {
+            let regs = &mut mbrs::asc![
+                RegDesc {
+                    address: 0x10,
+                    write: Some(WriteMethod::Ref(&mut reg1)),
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x11,
+                    write: Some(WriteMethod::Str {
+                        size: 1,
+                        sink: StrSink::Fn(Box::new(|s| name = s.to_string())),
+                    }),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            let buf = [
+                0x10, // Fc: Write multiple registers
+                0x00, 0x10, // Start address
+                0x00, 0x02, // Quantity
+                0x04, // Byte count
+                0x12, 0x34, // Register
+                0xC3, 0xA6, // Non ASCII text
+            ];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[0], 0x10 | 0x80); // Error response
+            assert_eq!(res[1], 0x03); // Illegal data value
+        }
+
+        assert_eq!(reg1, 0);
+        assert_eq!(name, "name");
+    }
+
+    #[test]
+    fn reg_read_scaled_works() {
+        let temperature = -12.3;
+        let cases = [
+            (
+                Scale {
+                    factor: 0.1,
+                    signed: true,
+                    ..Default::default()
+                },
+                0xFF85, // -123
+            ),
+            (
+                Scale {
+                    factor: 0.01,
+                    offset: -20.0,
+                    ..Default::default()
+                },
+                770,
+            ),
+            (
+                // Saturates at the limit of the raw type
+                Scale {
+                    factor: 0.0001,
+                    signed: true,
+                    ..Default::default()
+                },
+                0x8000,
+            ),
+        ];
+
+        for (scale, expected) in cases {
+            let reg = RegDesc {
+                address: 0x00,
+                read: Some(ReadMethod::Scaled(Source::Ref(&temperature))),
+                scale,
+                ..Default::default()
+            };
+
+            let mut buf = [0; 2];
+            reg.read(&mut buf).unwrap();
+            assert_eq!(u16::from_be_bytes(buf), expected);
+        }
+    }
+
+    #[test]
+    fn reg_write_scaled_range_works() {
+        let mut setpoint = 50.0;
+
+        {
+            let regs = &mut mbrs::asc![RegDesc {
+                address: 0x00,
+                write: Some(WriteMethod::Scaled(Sink::Ref(&mut setpoint))),
+                scale: Scale {
+                    factor: 0.1,
+                    min: Some(10.0),
+                    max: Some(90.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            // 95.0 and 9.9 are out of range
+            for raw in [950u16, 99] {
+                let [hi, lo] = raw.to_be_bytes();
+                let buf = [0x06, 0x00, 0x00, hi, lo];
+                let mut res = [0; mbrs::pdu::SIZE_MAX];
+                let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+                assert_eq!(res_len, 2);
+                assert_eq!(res[0], 0x06 | 0x80); // Error response
+                assert_eq!(res[1], 0x03); // Illegal data value
+            }
+
+            let buf = [
+                0x06, // Fc: Write single register
+                0x00, 0x00, // Address
+                0x01, 0x90, // 40.0
+            ];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+        }
+
+        assert_eq!(setpoint, 40.0);
+    }
+
+    #[test]
+    fn reg_blocks_work() {
+        let mut block = [0x0001, 0x0002, 0x0003, 0x0004];
+
+        {
+            let regs = &mut mbrs::asc![
+                RegDesc {
+                    address: 0x00,
+                    read: Some(ReadMethod::Value(0xAAAA)),
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x01,
+                    write: Some(WriteMethod::Block(&mut block)),
+                    ..Default::default()
+                },
+                RegDesc {
+                    address: 0x05,
+                    read: Some(ReadMethod::U32(Source::Value(0x0005_0006))),
+                    ..Default::default()
+                },
+            ];
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                ..Default::default()
+            };
+
+            // Middle of the block up to the end of the u32
+            let buf = [0x03, 0x00, 0x03, 0x00, 0x04];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 10);
+            assert_eq!(res[2..10], [0x00, 0x03, 0x00, 0x04, 0x00, 0x05, 0x00, 0x06]);
+
+            // Blocks can be accessed partially, other values can't
+            let buf = [0x03, 0x00, 0x02, 0x00, 0x04];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 2);
+            assert_eq!(res[..2], [0x03 | 0x80, 0x02]);
+
+            let buf = [0x10, 0x00, 0x02, 0x00, 0x02, 0x04, 0x12, 0x34, 0x56, 0x78];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+
+            let buf = [0x06, 0x00, 0x04, 0xAB, 0xCD];
+            let mut res = [0; mbrs::pdu::SIZE_MAX];
+            let res_len = mbrs::pdu::handle_req(&mut inst, &buf, &mut res).size();
+            assert_eq!(res_len, 5);
+        }
+
+        assert_eq!(block, [0x0001, 0x1234, 0x5678, 0xABCD]);
+    }
+}
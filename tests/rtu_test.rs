@@ -0,0 +1,166 @@
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use mbrs::rtu::{FrameReceiver, Timing};
+
+    fn us(us: u64) -> Duration {
+        Duration::from_micros(us)
+    }
+
+    /// Feed bytes back to back starting at `start`, returns the time of the last byte
+    fn feed(rx: &mut FrameReceiver, bytes: &[u8], start: Duration) -> Duration {
+        let mut now = start;
+        for &byte in bytes {
+            now += rx.timing().char_time;
+            assert!(rx.push(byte, now).is_none());
+        }
+        now
+    }
+
+    #[test]
+    fn rtu_timing_works() {
+        let timing = Timing::from_baud(9600);
+        assert_eq!(timing.char_time, Duration::from_nanos(1_145_833));
+        assert_eq!(timing.t1_5, Duration::from_nanos(1_718_749));
+        assert_eq!(timing.t3_5, Duration::from_nanos(4_010_415));
+
+        let timing = Timing::from_baud(19200);
+        assert_eq!(timing.t3_5, Duration::from_nanos(2_005_206));
+
+        let timing = Timing::from_baud(115200);
+        assert_eq!(timing.t1_5, us(750));
+        assert_eq!(timing.t3_5, us(1750));
+    }
+
+    #[test]
+    fn rtu_timing_from_config_works() {
+        // 8E1 and 8N2 are 11 bits like the default
+        let config = mbrs::SerialConfig::new(1);
+        assert_eq!(Timing::from_config(&config), Timing::from_baud(19200));
+
+        let config = mbrs::SerialConfig {
+            parity: mbrs::Parity::None,
+            stop_bits: 2,
+            ..mbrs::SerialConfig::new(1)
+        };
+        assert_eq!(Timing::from_config(&config), Timing::from_baud(19200));
+
+        // 8N1 is 10 bits
+        let config = mbrs::SerialConfig {
+            baud: 9600,
+            parity: mbrs::Parity::None,
+            ..mbrs::SerialConfig::new(1)
+        };
+        let timing = Timing::from_config(&config);
+        assert_eq!(timing.char_time, Duration::from_nanos(1_041_666));
+        assert_eq!(timing.t3_5, Duration::from_nanos(3_645_831));
+    }
+
+    #[test]
+    fn rtu_poll_works() {
+        let mut rx = FrameReceiver::new(9600);
+        let end = feed(&mut rx, &[0x01, 0x03, 0x00, 0x00], Duration::ZERO);
+
+        assert!(rx.poll(end + us(4000)).is_none());
+        let frame = rx.poll(end + us(4011)).unwrap();
+        assert_eq!(&frame[..], [0x01, 0x03, 0x00, 0x00]);
+        assert!(frame.is_valid());
+
+        // Nothing left
+        assert!(rx.poll(end + us(10_000)).is_none());
+    }
+
+    #[test]
+    fn rtu_push_ends_frame_works() {
+        let mut rx = FrameReceiver::new(115200);
+        let end = feed(&mut rx, &[0x01, 0x02], Duration::ZERO);
+
+        // A byte after t3.5 starts a new frame
+        let frame = rx.push(0x03, end + us(96 + 1750)).unwrap();
+        assert_eq!(&frame[..], [0x01, 0x02]);
+
+        let frame = rx.poll(end + us(10_000)).unwrap();
+        assert_eq!(&frame[..], [0x03]);
+    }
+
+    #[test]
+    fn rtu_gap_violation_works() {
+        let mut rx = FrameReceiver::new(115200);
+        let end = feed(&mut rx, &[0x01, 0x02], Duration::ZERO);
+
+        // Silence between t1.5 and t3.5 within a frame
+        assert!(rx.push(0x03, end + us(96 + 1000)).is_none());
+        let frame = rx.poll(end + us(10_000)).unwrap();
+        assert_eq!(&frame[..], [0x01, 0x02, 0x03]);
+        assert!(frame.gap_violation());
+        assert!(!frame.is_valid());
+
+        // The next frame starts out clean
+        let end = feed(&mut rx, &[0x04], end + us(20_000));
+        let frame = rx.poll(end + us(2000)).unwrap();
+        assert!(frame.is_valid());
+    }
+
+    #[test]
+    fn rtu_push_all_split_frame_works() {
+        let req = [0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A];
+
+        for split in 1..req.len() {
+            let mut rx = FrameReceiver::new(9600);
+            let char_time = rx.timing().char_time;
+
+            // Sent back to back, but read in two chunks as the FIFO fills
+            let start = us(1000);
+            let (head, tail) = req.split_at(split);
+            assert!(rx
+                .push_all(head, start + char_time * head.len() as u32)
+                .is_none());
+            let end = start + char_time * req.len() as u32;
+            assert!(rx.push_all(tail, end).is_none());
+
+            let frame = rx.poll(end + rx.timing().t3_5).unwrap();
+            assert_eq!(&frame[..], req);
+            assert!(frame.is_valid());
+        }
+    }
+
+    #[test]
+    fn rtu_overflow_works() {
+        let mut rx = FrameReceiver::new(115200);
+        assert!(rx.push_all(&[0xAA; 300], us(100)).is_none());
+
+        let frame = rx.poll(us(5000)).unwrap();
+        assert_eq!(frame.len(), mbrs::adu::SIZE_MAX);
+        assert!(frame.overflow());
+    }
+
+    #[test]
+    fn rtu_handle_frame_works() {
+        use mbrs::reg::Descriptor as RegDesc;
+        use mbrs::reg::ReadMethod as RegReadMethod;
+
+        let regs = &mut mbrs::asc![RegDesc {
+            address: 0x00,
+            read: Some(RegReadMethod::Value(0x1234)),
+            ..Default::default()
+        }];
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            serial: Some(mbrs::SerialConfig::new(1)),
+            ..Default::default()
+        };
+
+        let mut req = vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x01];
+        req.extend(mbrs::crc::crc16(&req).to_le_bytes());
+
+        let mut rx = FrameReceiver::new(19200);
+        let end = feed(&mut rx, &req, us(1000));
+        let frame = rx.poll(end + rx.timing().t3_5).unwrap();
+
+        let mut res = [0; mbrs::adu::SIZE_MAX];
+        let res_len = mbrs::adu::handle_req(&mut inst, &frame, &mut res).size();
+        assert_eq!(res_len, 7);
+        assert_eq!(res[3..5], [0x12, 0x34]);
+    }
+}
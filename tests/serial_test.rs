@@ -0,0 +1,171 @@
+// End-to-end tests over a pseudo-terminal pair, run with `cargo test --features serial`
+#[cfg(all(test, feature = "serial", target_os = "linux"))]
+mod test {
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::fd::FromRawFd;
+    use std::time::Duration;
+
+    use mbrs::reg::Descriptor as RegDesc;
+    use mbrs::reg::WriteMethod as RegWriteMethod;
+    use mbrs::serial::Server;
+    use mbrs::{Outcome, Reason};
+
+    /// Open a pty pair, returns the master and the path of the slave device
+    fn pty() -> (File, String) {
+        // SAFETY: plain libc calls on the fd they return
+        unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(fd >= 0);
+            assert_eq!(libc::grantpt(fd), 0);
+            assert_eq!(libc::unlockpt(fd), 0);
+
+            let mut name = [0; 64];
+            assert_eq!(libc::ptsname_r(fd, name.as_mut_ptr(), name.len()), 0);
+            let path = CStr::from_ptr(name.as_ptr()).to_str().unwrap().to_owned();
+
+            (File::from_raw_fd(fd), path)
+        }
+    }
+
+    fn frame(pdu: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0x01];
+        buf.extend(pdu);
+        buf.extend(mbrs::crc::crc16(&buf).to_le_bytes());
+        buf
+    }
+
+    fn read_exact(master: &mut File, size: usize) -> Vec<u8> {
+        let mut buf = vec![0; size];
+        master.read_exact(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn serial_server_works() {
+        let (mut master, path) = pty();
+
+        let mut values = [0x1234u16];
+        {
+            let regs = &mut mbrs::asc![RegDesc {
+                address: 0x00,
+                write: Some(RegWriteMethod::Block(&mut values)),
+                ..Default::default()
+            }];
+            let config = mbrs::SerialConfig::new(1);
+            let mut inst = mbrs::Instance {
+                holding_regs: Some(regs),
+                serial: Some(config),
+                ..Default::default()
+            };
+            let mut server = Server::open(&path, &config).unwrap();
+
+            let client = std::thread::spawn(move || {
+                master
+                    .write_all(&frame(&[0x03, 0x00, 0x00, 0x00, 0x01]))
+                    .unwrap();
+                let read = read_exact(&mut master, 7);
+
+                let req = frame(&[0x06, 0x00, 0x00, 0xAB, 0xCD]);
+                master.write_all(&req).unwrap();
+                let write = read_exact(&mut master, 8);
+                assert_eq!(write, req);
+
+                // Bad CRC, no response
+                master
+                    .write_all(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00])
+                    .unwrap();
+
+                // Keep the master open until the server is done with it
+                (read, master)
+            });
+
+            let timeout = Duration::from_secs(5);
+            for outcome in [
+                Outcome::Response(7),
+                Outcome::Response(8),
+                Outcome::NoResponse(Reason::Crc),
+            ] {
+                assert_eq!(server.poll(&mut inst, timeout).unwrap(), Some(outcome));
+            }
+            assert_eq!(
+                server.poll(&mut inst, Duration::from_millis(50)).unwrap(),
+                None
+            );
+
+            let (read, _master) = client.join().unwrap();
+            assert_eq!(read, frame(&[0x03, 0x02, 0x12, 0x34]));
+        }
+        assert_eq!(values, [0xABCD]);
+    }
+
+    #[test]
+    fn serial_server_chunked_request_works() {
+        let (mut master, path) = pty();
+
+        let mut values = [0x1234u16];
+        let regs = &mut mbrs::asc![RegDesc {
+            address: 0x00,
+            write: Some(RegWriteMethod::Block(&mut values)),
+            ..Default::default()
+        }];
+        let config = mbrs::SerialConfig {
+            baud: 1200,
+            ..mbrs::SerialConfig::new(1)
+        };
+        let mut inst = mbrs::Instance {
+            holding_regs: Some(regs),
+            serial: Some(config),
+            ..Default::default()
+        };
+        let mut server = Server::open(&path, &config).unwrap();
+        let char_time = mbrs::rtu::Timing::from_config(&config).char_time;
+
+        // Bytes trickle in as a UART FIFO hands them over, three at a time.
+        // The pauses are longer than t1.5 but shorter than the three characters took on the line.
+        let client = std::thread::spawn(move || {
+            let req = frame(&[0x03, 0x00, 0x00, 0x00, 0x01]);
+            for chunk in req.chunks(3) {
+                master.write_all(chunk).unwrap();
+                std::thread::sleep(char_time * 5 / 2);
+            }
+
+            let read = read_exact(&mut master, 7);
+            (read, master)
+        });
+
+        let outcome = server.poll(&mut inst, Duration::from_secs(5)).unwrap();
+        assert_eq!(outcome, Some(Outcome::Response(7)));
+
+        let (read, _master) = client.join().unwrap();
+        assert_eq!(read, frame(&[0x03, 0x02, 0x12, 0x34]));
+    }
+
+    #[test]
+    fn serial_config_fails() {
+        let (_master, path) = pty();
+
+        for config in [
+            mbrs::SerialConfig {
+                baud: 12345,
+                ..mbrs::SerialConfig::new(1)
+            },
+            mbrs::SerialConfig {
+                data_bits: 7,
+                ..mbrs::SerialConfig::new(1)
+            },
+            mbrs::SerialConfig {
+                data_bits: 9,
+                ..mbrs::SerialConfig::new(1)
+            },
+            mbrs::SerialConfig {
+                stop_bits: 0,
+                ..mbrs::SerialConfig::new(1)
+            },
+        ] {
+            let err = Server::open(&path, &config).err().unwrap();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+}